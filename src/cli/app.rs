@@ -0,0 +1,602 @@
+use super::DownloadOpts;
+use crate::{
+    export::{file_name, write_order, Order},
+    local::LocalState,
+    manifest::Manifest,
+    prelude::*,
+    steamcmd::{DownloadOutcome, DownloadReport, Retry, SteamCmd},
+    util::PrettyCmd,
+    workshop::{ReviewOptions, WFile, WFiles},
+};
+use itertools::Itertools;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use std::{env::current_dir, time::Duration};
+
+/// The downloads chosen for a run, and the collection each one was requested through.
+#[derive(Debug, Default)]
+pub struct Plan {
+    /// One script per download location. The first is for the location set with `-o`,
+    /// followed by those of app ids routed elsewhere with `--output-for`.
+    pub scripts: Vec<SteamCmd>,
+    pub sources: HashMap<FileId, FileId>,
+    /// The mod orders to save with `--save`: each reviewed collection and its mods,
+    /// with `None` for the mods requested on their own, which are saved per app.
+    pub orders: Vec<(Option<FileId>, Vec<FileId>)>,
+}
+
+impl Plan {
+    /// The script downloading into `install_dir`, added on first use.
+    fn script_for(&mut self, install_dir: Option<&Path>, username: &str) -> &mut SteamCmd {
+        let i = match self
+            .scripts
+            .iter()
+            .position(|script| script.get_install_dir() == install_dir)
+        {
+            Some(i) => i,
+            None => {
+                let mut script = SteamCmd::new();
+                if let Some(path) = install_dir {
+                    script.install_dir(path);
+                }
+                script.login(username);
+                self.scripts.push(script);
+                self.scripts.len() - 1
+            }
+        };
+        &mut self.scripts[i]
+    }
+}
+
+/// A download run: the download options and requested file ids, together with the items they resolve to.
+#[derive(Debug)]
+pub struct App {
+    pub opts: DownloadOpts,
+    pub files: Vec<FileId>,
+    pub wfiles: WFiles,
+}
+
+impl App {
+    pub fn new(opts: DownloadOpts, files: Vec<FileId>) -> Result<Self> {
+        let wfiles = WFiles::resolve(&files, opts.max_depth())?;
+        Self::with_wfiles(opts, files, wfiles)
+    }
+
+    /// Set up a run from items that were already resolved. Fails on invalid ids with `--strict`.
+    pub fn with_wfiles(opts: DownloadOpts, mut files: Vec<FileId>, wfiles: WFiles) -> Result<Self> {
+        if opts.strict {
+            wfiles.ensure_valid(&files)?;
+        }
+        // Remove invalid mod ids
+        files.retain(|id| wfiles.contains(id));
+        Ok(Self {
+            opts,
+            files,
+            wfiles,
+        })
+    }
+
+    /// Prompt for the requested items, and queue the downloads and the mod orders to save.
+    pub fn build_plan(&self) -> Result<Plan> {
+        let mut plan = Plan::default();
+        plan.script_for(self.opts.output.as_deref(), self.opts.username());
+
+        if self.opts.update && self.opts.output.is_none() && self.opts.output_for.is_empty() {
+            return Err(color_eyre::eyre::eyre!(
+                "--update needs the download location set with -o"
+            ));
+        }
+        let mut local = HashMap::new();
+
+        let mut standalone = Vec::new();
+        for file_id in self.files.iter() {
+            let file = &self.wfiles.all_files[file_id];
+            if ReviewOptions::Yes != file.prompt(self.opts.review)? {
+                continue;
+            }
+
+            if !file.is_collection() {
+                self.queue(&mut plan, &mut local, file)?;
+                standalone.push(file.file_id);
+            } else {
+                let mut mods = Vec::new();
+                self.wfiles.expand_collection(
+                    file,
+                    self.opts.review,
+                    &mut HashSet::new(),
+                    &mut mods,
+                )?;
+
+                plan.orders.push((
+                    Some(file.file_id),
+                    mods.iter().map(|file| file.file_id).collect(),
+                ));
+
+                for inner_file in mods {
+                    self.queue(&mut plan, &mut local, inner_file)?;
+                    plan.sources
+                        .entry(inner_file.file_id)
+                        .or_insert(file.file_id);
+                }
+            }
+        }
+
+        // mods requested on their own are saved in command-line order
+        if !standalone.is_empty() {
+            plan.orders.push((None, standalone));
+        }
+
+        Ok(plan)
+    }
+
+    /// Write the mod orders of `plan` if `--save` is set. Game formats read mod IDs from the installed items,
+    /// so this runs after the downloads.
+    pub fn save_orders(&self, plan: &Plan) -> Result<()> {
+        if self.opts.save.is_none() {
+            return Ok(());
+        }
+        let save_dir = match self.opts.save_to.save_dir.as_deref() {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                dir.to_path_buf()
+            }
+            None => current_dir()?,
+        };
+
+        for (collection, mods) in plan.orders.iter() {
+            let mods = mods
+                .iter()
+                .map(|id| &self.wfiles.all_files[id])
+                .collect::<Vec<_>>();
+            match collection {
+                Some(id) => {
+                    let collection = Order::Collection(&self.wfiles.all_files[id]);
+                    self.save_order(&save_dir, collection, &mods)?;
+                }
+                None => {
+                    for app_id in mods.iter().map(|file| file.app_id).unique() {
+                        let app_mods = mods
+                            .iter()
+                            .copied()
+                            .filter(|file| file.app_id == app_id)
+                            .collect::<Vec<_>>();
+                        self.save_order(&save_dir, Order::Standalone { app_id }, &app_mods)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the order of `mods` in the format set with `--save`.
+    fn save_order(&self, save_dir: &Path, order: Order, mods: &[&WFile]) -> Result<()> {
+        let Some(format) = self.opts.save else {
+            return Ok(());
+        };
+        let path = save_dir.join(file_name(
+            self.opts.save_to.save_name(),
+            order,
+            mods,
+            format,
+        )?);
+        write_order(
+            &mut BufWriter::new(File::create(path)?),
+            format,
+            &self.wfiles,
+            order,
+            mods,
+            self.opts.install_dir_for(order.app_id()),
+        )
+    }
+
+    /// The script for the download location set with `-o`, ignoring items routed elsewhere with `--output-for`.
+    pub fn build_script(&self) -> Result<SteamCmd> {
+        Ok(self.build_plan()?.scripts.swap_remove(0))
+    }
+
+    /// Queue the download of `file` into the download location of its app, unless the installed version is already up to date.
+    fn queue(
+        &self,
+        plan: &mut Plan,
+        local: &mut HashMap<PathBuf, LocalState>,
+        file: &WFile,
+    ) -> Result<()> {
+        let install_dir = self.opts.install_dir_for(file.app_id);
+        if self.opts.update {
+            let install_dir = install_dir.ok_or_else(|| {
+                color_eyre::eyre::eyre!(
+                    "--update needs a download location for app {}, set with -o or --output-for",
+                    file.app_id
+                )
+            })?;
+            let local = local
+                .entry(install_dir.to_path_buf())
+                .or_insert_with(|| LocalState::new(install_dir));
+            if local.is_up_to_date(file) {
+                println!("Up to date \t {}: \t {}", file.file_id, file.title);
+                return Ok(());
+            }
+        }
+        plan.script_for(install_dir, self.opts.username())
+            .download(file.app_id, file.file_id);
+        Ok(())
+    }
+
+    pub fn build_cmd(&self) -> Result<Command> {
+        Ok(self.build_script()?.build())
+    }
+
+    pub fn run(&self) -> Result<()> {
+        let plan = self.build_plan()?;
+        let mut failed = 0;
+        let mut total = 0;
+
+        for (i, script) in plan.scripts.iter().enumerate() {
+            if script.get_downloads().is_empty() {
+                continue;
+            }
+
+            let mut cmd = match self.opts.runscript.as_deref() {
+                Some(path) => {
+                    // steamcmd looks up relative script paths in its own directory
+                    let mut path = current_dir()?.join(path);
+                    if i > 0 {
+                        path = numbered(&path, i);
+                    }
+                    script.write_script(&path)?;
+                    println!("\nSteamCMD script written to {}", path.display());
+                    script.runscript(path)
+                }
+                None => script.build(),
+            };
+
+            if !self.opts.exec {
+                println!("\n{}", PrettyCmd::new(&cmd));
+                continue;
+            }
+
+            let retry = Retry {
+                attempts: self.opts.retries(),
+                delay: Duration::from_secs(self.opts.retry_delay()),
+            };
+            let report = script.execute_with_retry(&mut cmd, &retry)?;
+            self.print_summary(&report);
+
+            if let Some(path) = script.get_install_dir() {
+                let mut manifest = Manifest::load(path)?;
+                manifest.record(&report, &self.wfiles, &plan.sources);
+                manifest.save(path)?;
+            }
+
+            failed += report.failed().count();
+            total += report.items.len();
+        }
+
+        if plan
+            .scripts
+            .iter()
+            .all(|script| script.get_downloads().is_empty())
+        {
+            println!("\nNothing to download");
+        }
+        self.save_orders(&plan)?;
+        if failed > 0 {
+            return Err(color_eyre::eyre::eyre!(
+                "{} of {} downloads failed",
+                failed,
+                total
+            ));
+        }
+        Ok(())
+    }
+
+    fn print_summary(&self, report: &DownloadReport) {
+        println!("\nDownload summary:");
+        for item in report.items.iter() {
+            let title = self
+                .wfiles
+                .get(&item.file_id)
+                .map_or("", |file| file.title.as_str());
+            match &item.outcome {
+                DownloadOutcome::Success(path) => println!(
+                    "Succeeded \t {} \t {} \t {} \t {}",
+                    item.app_id,
+                    item.file_id,
+                    title,
+                    path.display()
+                ),
+                DownloadOutcome::Failure(reason) => println!(
+                    "Failed \t\t {} \t {} \t {} \t ({})",
+                    item.app_id, item.file_id, title, reason
+                ),
+            }
+        }
+        println!(
+            "{} succeeded, {} failed",
+            report.succeeded().count(),
+            report.failed().count()
+        );
+    }
+}
+
+/// `path` with `_<n>` appended to its file stem, for the scripts of further download locations.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("_{}", n));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempPath;
+    use crate::{cli::Params, net::mock};
+    use structopt::StructOpt;
+
+    fn new_app<I>(args: I) -> Result<App>
+    where
+        I: IntoIterator,
+        I::Item: Into<std::ffi::OsString> + Clone,
+    {
+        let params = Params::from_iter(args);
+        App::new(params.download, params.lists.read(params.files)?)
+    }
+
+    fn download_args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .filter_map(|arg| arg.to_str())
+            .filter_map(|arg| arg.strip_prefix("+workshop_download_item "))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_collection() -> Result<()> {
+        mock::serve();
+        let app = new_app(["swd", "368330611"])?;
+
+        // sorted by sortorder, linked collection expanded in place, duplicates and the invalid 1111 dropped
+        assert_eq!(
+            download_args(&app.build_cmd()?),
+            [
+                "294100 818773962",
+                "294100 2009463077",
+                "294100 1541438856",
+                "294100 761421485",
+            ]
+        );
+        app.run()
+    }
+
+    #[test]
+    fn test_max_depth() -> Result<()> {
+        mock::serve();
+        let app = new_app(["swd", "--max-depth", "0", "368330611"])?;
+
+        assert!(!app.wfiles.contains(&1541984105));
+        assert_eq!(
+            download_args(&app.build_cmd()?),
+            ["294100 818773962", "294100 2009463077", "294100 761421485"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_id() -> Result<()> {
+        mock::serve();
+        let app = new_app(["swd", "1111", "2824342092", "1626860092"])?;
+
+        assert_eq!(app.files, [2824342092, 1626860092]);
+        assert_eq!(
+            download_args(&app.build_cmd()?),
+            ["294100 2824342092", "294100 2529002857"]
+        );
+
+        assert!(new_app(["swd", "--strict", "1111", "2824342092"]).is_err());
+        assert!(new_app(["swd", "--strict", "2824342092"]).is_ok());
+
+        // a collection whose lookup failed is neither downloaded as a mod nor accepted with --strict
+        let app = new_app(["swd", "1113", "2824342092"])?;
+        assert_eq!(app.files, [2824342092]);
+        assert_eq!(download_args(&app.build_cmd()?), ["294100 2824342092"]);
+        assert!(new_app(["swd", "--strict", "1113", "2824342092"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_output() -> Result<()> {
+        mock::serve();
+        let app = new_app([
+            "swd",
+            "-o",
+            r"C:\User\admin\My Documents\My Mods",
+            "-u",
+            "someone",
+            "368330611",
+        ])?;
+        let cmd = app.build_cmd()?;
+        let args = cmd
+            .get_args()
+            .take(4)
+            .filter_map(|arg| arg.to_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(cmd.get_program(), "steamcmd");
+        assert_eq!(
+            args,
+            [
+                "+force_install_dir",
+                r"C:\User\admin\My Documents\My Mods",
+                "+login",
+                "someone"
+            ]
+        );
+        assert_eq!(cmd.get_args().last().unwrap(), "+quit");
+        app.run()
+    }
+
+    #[test]
+    fn test_runscript() -> Result<()> {
+        mock::serve();
+        let path = TempPath::new("runscript.txt");
+        let app = new_app([
+            "swd".as_ref(),
+            "--runscript".as_ref(),
+            path.as_os_str(),
+            "2824342092".as_ref(),
+        ])?;
+        app.run()?;
+
+        let script = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            script,
+            "login anonymous\nworkshop_download_item 294100 2824342092\nquit\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_for() -> Result<()> {
+        mock::serve();
+        let path = TempPath::new("output_for.txt");
+        let app = new_app([
+            "swd".as_ref(),
+            "-o".as_ref(),
+            "/srv/mods".as_ref(),
+            "--output-for".as_ref(),
+            "108600=/srv/zomboid".as_ref(),
+            "--runscript".as_ref(),
+            path.as_os_str(),
+            "2824342092".as_ref(),
+            "2169435993".as_ref(),
+        ])?;
+
+        let plan = app.build_plan()?;
+        assert_eq!(
+            plan.scripts
+                .iter()
+                .map(|script| (script.get_install_dir(), script.get_downloads()))
+                .collect::<Vec<_>>(),
+            [
+                (Some(Path::new("/srv/mods")), &[(294100, 2824342092)][..]),
+                (Some(Path::new("/srv/zomboid")), &[(108600, 2169435993)][..]),
+            ]
+        );
+
+        app.run()?;
+        let numbered = numbered(&path, 1);
+        let script = fs::read_to_string(&numbered)?;
+        fs::remove_file(&path)?;
+        fs::remove_file(&numbered)?;
+        assert!(numbered.to_string_lossy().ends_with("output_for_1.txt"));
+        assert!(script.contains("workshop_download_item 108600 2169435993"));
+
+        // no script is written or run for a location with nothing to download
+        new_app([
+            "swd".as_ref(),
+            "--output-for".as_ref(),
+            "108600=/srv/zomboid".as_ref(),
+            "--runscript".as_ref(),
+            path.as_os_str(),
+            "2169435993".as_ref(),
+        ])?
+        .run()?;
+        assert!(!path.exists());
+        fs::remove_file(&numbered)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_update() -> Result<()> {
+        mock::serve();
+        let dir = TempPath::new("update");
+        let workshop = dir.join("steamapps").join("workshop");
+        std::fs::create_dir_all(workshop.join("content").join("294100").join("761421485"))?;
+        // 818773962 is installed at its latest version, 2009463077 at an older one,
+        // 761421485 has no record but its folder is newer than the latest version
+        std::fs::write(
+            workshop.join("appworkshop_294100.acf"),
+            r#""AppWorkshop"
+{
+	"WorkshopItemsInstalled"
+	{
+		"818773962" { "timeupdated" "1658102400" }
+		"2009463077" { "timeupdated" "1600000000" }
+	}
+}"#,
+        )?;
+
+        let app = new_app([
+            "swd".as_ref(),
+            "--update".as_ref(),
+            "-o".as_ref(),
+            dir.as_os_str(),
+            "368330611".as_ref(),
+        ])?;
+        let downloads = download_args(&app.build_cmd()?);
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(downloads, ["294100 2009463077", "294100 1541438856"]);
+
+        assert!(new_app(["swd", "--update", "368330611"])?
+            .build_cmd()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sources() -> Result<()> {
+        mock::serve();
+        let plan = new_app(["swd", "2824342092", "368330611", "1626860092"])?.build_plan()?;
+
+        assert_eq!(plan.sources.get(&2824342092), Some(&1626860092));
+        assert_eq!(plan.sources.get(&1541438856), Some(&368330611));
+        assert_eq!(plan.sources.len(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_standalone() -> Result<()> {
+        mock::serve();
+        let dir = TempPath::new("save_standalone");
+        new_app([
+            "swd".as_ref(),
+            "--save=csv".as_ref(),
+            "--save-dir".as_ref(),
+            dir.as_os_str(),
+            "2824342092".as_ref(),
+            "1626860092".as_ref(),
+            "2169435993".as_ref(),
+            "761421485".as_ref(),
+        ])?
+        .run()?;
+
+        let collection = fs::read_to_string(dir.join("1626860092 Small Collection.csv"))?;
+        let standalone = fs::read_to_string(dir.join("standalone-294100 Standalone mods.csv"))?;
+        // mods of another app are saved separately
+        let zomboid = fs::read_to_string(dir.join("standalone-108600 Standalone mods.csv"))?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(collection.lines().count(), 3);
+        assert_eq!(
+            standalone
+                .lines()
+                .skip(1)
+                .map(|line| line.split(',').take(2).collect::<Vec<_>>().join(","))
+                .collect::<Vec<_>>(),
+            ["1,2824342092", "2,761421485"]
+        );
+        assert_eq!(zomboid.lines().count(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,1299 @@
+mod app;
+
+pub use app::{App, Plan};
+
+use crate::{
+    config::{Config, Profile},
+    export::{SaveFormat, DEFAULT_FILE_NAME},
+    input::{parse_file_arg, read_file_ids_from, FileArg},
+    lock::{Lock, LOCKFILE_NAME},
+    manifest::Manifest,
+    net::{self, get_published_file_details::Metadata},
+    prelude::*,
+    workshop::{parse_file_id, WFiles},
+    workspace::{Workspace, WORKSPACE_NAME},
+};
+use color_eyre::eyre::WrapErr;
+use itertools::Itertools;
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use structopt::StructOpt;
+
+///
+/// (This software has not been extensively tested, use at your own risk. Require steamcmd under PATH)
+///
+/// You would need [SteamCMD](https://developer.valvesoftware.com/wiki/SteamCMD#Downloading_SteamCMD) to use this software, and remember to include it under the PATH environment variable.
+/// A command-line utility to download workshop item and collections from steam workshop.
+/// This software assembles a command for SteamCMD to execute. By default, this command is only printed to standard output, you need to use the `-e` flag to automatically execute the command.
+/// The default download directory is /path/to/steamcmd/steamapps/workshop/content/. You can set an alternative location with `-o`.
+/// Without a subcommand, `swd <files>` downloads the given mods and collections, the same as `swd download <files>`.
+#[derive(Debug, StructOpt)]
+pub struct Params {
+    #[structopt(flatten)]
+    pub download: DownloadOpts,
+
+    /// Base URL of the Steam Web API, e.g. to use a local mirror.
+    #[structopt(long, global = true, name = "url")]
+    pub api_url: Option<String>,
+
+    /// Number of file IDs sent to the Steam Web API per request. [default: 100]
+    #[structopt(long, global = true, name = "ids")]
+    pub api_batch_size: Option<usize>,
+
+    /// Number of requests to the Steam Web API sent at once. [default: 4]
+    #[structopt(long, global = true, name = "requests")]
+    pub api_concurrency: Option<usize>,
+
+    /// Steam Web API key, needed by the `published-file-service` backend.
+    #[structopt(long, global = true, name = "key", env = net::API_KEY_VAR, hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// Endpoint to retrieve the details of workshop items from. `remote-storage` needs no API key,
+    /// `published-file-service` needs one and also returns required items, votes, and private items the key can access. [default: remote-storage]
+    #[structopt(
+        long,
+        global = true,
+        name = "backend",
+        possible_values(net::Backend::NAMES)
+    )]
+    pub api_backend: Option<net::Backend>,
+
+    /// Seconds to wait for a response from the Steam Web API before giving up on a request. [default: 30]
+    #[structopt(long, global = true, name = "timeout")]
+    pub api_timeout: Option<u64>,
+
+    /// Number of times a request to the Steam Web API is re-sent after a timeout, connection error, rate limit or server error. [default: 3]
+    #[structopt(long, global = true, name = "count")]
+    pub api_retries: Option<u32>,
+
+    /// Read defaults for the options from this config file instead of $XDG_CONFIG_HOME/swd/config.toml.
+    #[structopt(long, global = true, name = "config")]
+    pub config: Option<PathBuf>,
+
+    /// Use the options of a profile defined in the config file. Options given on the command line take precedence.
+    #[structopt(long, global = true, name = "profile")]
+    pub profile: Option<String>,
+
+    /// File IDs or workshop URLs of the mods and collections to download, `-` reads them from standard input.
+    /// The file ID can be found at the end of the url for each workshop item, e.g. https://steamcommunity.com/sharedfiles/filedetails/?id=<file ID>
+    #[structopt(parse(try_from_str = parse_file_arg))]
+    pub files: Vec<FileArg>,
+
+    #[structopt(flatten)]
+    pub lists: FileLists,
+
+    #[structopt(subcommand)]
+    pub cmd: Option<Subcommand>,
+}
+
+// Options controlling how the resolved items are downloaded, shared by the top-level command and `install`.
+// A plain comment, a doc comment would replace the about text of the commands flattening it.
+#[derive(Debug, PartialEq, StructOpt)]
+pub struct DownloadOpts {
+    /// Execute the produced command through steamcmd, otherwise the command is only printed to standard output and need to be executed manually.
+    #[structopt(short, long, overrides_with = "no-exec")]
+    pub exec: bool,
+
+    /// Only print the command, even if the config file sets `exec`.
+    #[structopt(long, overrides_with = "exec")]
+    pub no_exec: bool,
+
+    /// Review each mod one by one. Input yes/no/skip for each mod or collection.
+    /// The option 'skip', otherwise equivalent to 'no', can be used to skip rest of the mods in the context of a collection.
+    #[structopt(short, long)]
+    pub review: bool,
+
+    /// Steam username for non-anonymous download [default: anonymous]
+    #[structopt(short, long)]
+    pub username: Option<String>,
+
+    /// Set the path of the download location. The path will be passed to force_install_dir in SteamCMD.
+    #[structopt(short, long, name = "path")]
+    pub output: Option<PathBuf>,
+
+    /// Download the items of an app into their own location instead of the one set with `-o`, e.g. `--output-for 294100=/srv/rimworld`.
+    /// Can be repeated, and SteamCMD is run once for each download location.
+    #[structopt(long, name = "appid=path", number_of_values = 1, parse(try_from_str = parse_output_for))]
+    pub output_for: Vec<(FileId, PathBuf)>,
+
+    /// Save the mod orders of collections to specified format to the current working directory.
+    /// Mods given on their own are saved in command-line order, one list per app, with `{id}` standing for `standalone-<appid>`.
+    /// `simple` writes one file ID per line, `csv` and `tsv` write a table with a header row,
+    /// `json` and `toml` also include the collection and the `sortorder` of each mod.
+    /// `rimworld`, `zomboid` and `arma3` write the load order of the game, reading mod IDs from the items installed under `-o`. Orders are saved once the downloads are done.
+    #[structopt(
+        long,
+        takes_value(true),
+        require_equals(true),
+        possible_values(SaveFormat::NAMES),
+        name = "format"
+    )]
+    pub save: Option<SaveFormat>,
+
+    #[structopt(flatten)]
+    pub save_to: SaveTo,
+
+    /// Write the SteamCMD commands to a script file instead of passing them as arguments, and run it with `steamcmd +runscript`.
+    /// Avoids command-line length limits for large collections, and the script can be re-run later.
+    #[structopt(long, name = "file")]
+    pub runscript: Option<PathBuf>,
+
+    /// Only download items that were updated on the workshop since they were installed in the download location set with `-o`.
+    #[structopt(long)]
+    pub update: bool,
+
+    /// Fail if any of the requested file IDs is invalid, instead of reporting and leaving it out.
+    #[structopt(long, overrides_with = "no-strict")]
+    pub strict: bool,
+
+    /// Report and leave out invalid file IDs, even if the config file sets `strict`.
+    #[structopt(long, overrides_with = "strict")]
+    pub no_strict: bool,
+
+    /// Number of times to re-issue downloads that failed, only used with `-e`. [default: 0]
+    #[structopt(long, name = "attempts")]
+    pub retries: Option<u32>,
+
+    /// Seconds to wait before the first retry, doubled for every further retry. [default: 5]
+    #[structopt(long, name = "seconds")]
+    pub retry_delay: Option<u64>,
+
+    /// Maximum depth of linked collections to follow. Collections linked from a collection given on the command line are at depth 1. [default: 8]
+    #[structopt(long)]
+    pub max_depth: Option<usize>,
+}
+
+impl Default for DownloadOpts {
+    fn default() -> Self {
+        Self::from_iter(["swd"])
+    }
+}
+
+/// Maximum depth of linked collections followed unless set otherwise with `--max-depth` or the config file.
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// `--max-depth` of the commands without download options, falling back to the config file.
+fn max_depth(max_depth: Option<usize>, profile: &Profile) -> usize {
+    max_depth.or(profile.max_depth).unwrap_or(DEFAULT_MAX_DEPTH)
+}
+
+// Options left unset on the command line fall back to the config file, and then to these defaults.
+impl DownloadOpts {
+    pub fn username(&self) -> &str {
+        self.username.as_deref().unwrap_or("anonymous")
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries.unwrap_or(0)
+    }
+
+    pub fn retry_delay(&self) -> u64 {
+        self.retry_delay.unwrap_or(5)
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+    }
+
+    /// The download location of the items of `app_id`.
+    pub fn install_dir_for(&self, app_id: FileId) -> Option<&Path> {
+        self.output_for
+            .iter()
+            .find(|(app, _)| *app == app_id)
+            .map(|(_, path)| path.as_path())
+            .or(self.output.as_deref())
+    }
+
+    /// Fill in the options not given on the command line from a config profile.
+    pub fn apply(&mut self, profile: &Profile) {
+        self.exec |= !self.no_exec && profile.exec.unwrap_or_default();
+        self.strict |= !self.no_strict && profile.strict.unwrap_or_default();
+        self.username = self.username.take().or_else(|| profile.username.clone());
+        self.output = self.output.take().or_else(|| profile.output.clone());
+        for (&app_id, path) in profile.output_for.iter().flatten() {
+            if !self.output_for.iter().any(|(app, _)| *app == app_id) {
+                self.output_for.push((app_id, path.clone()));
+            }
+        }
+        self.save = self.save.or(profile.save);
+        self.retries = self.retries.or(profile.retries);
+        self.retry_delay = self.retry_delay.or(profile.retry_delay);
+        self.max_depth = self.max_depth.or(profile.max_depth);
+        self.save_to.apply(profile);
+    }
+}
+
+fn parse_output_for(s: &str) -> Result<(FileId, PathBuf)> {
+    let (app_id, path) = s
+        .split_once('=')
+        .filter(|(_, path)| !path.is_empty())
+        .ok_or_else(|| color_eyre::eyre::eyre!("expected <appid>=<path>, got '{}'", s))?;
+    let app_id = app_id
+        .trim()
+        .parse()
+        .map_err(|_| color_eyre::eyre::eyre!("invalid app id '{}'", app_id))?;
+    Ok((app_id, path.into()))
+}
+
+// Where saved mod orders are written, shared by `--save` and `save-order`.
+#[derive(Debug, PartialEq, StructOpt)]
+pub struct SaveTo {
+    /// Directory to save mod orders to, created if missing. Defaults to the current working directory.
+    #[structopt(long, name = "dir")]
+    pub save_dir: Option<PathBuf>,
+
+    /// File name of saved mod orders, without extension. Placeholders: {id} and {title} of the collection,
+    /// {date} for today and {updated} for when the collection was last updated. Characters not allowed in file names are replaced by `_`.
+    /// [default: "{id} {title}"]
+    #[structopt(long, name = "template")]
+    pub save_name: Option<String>,
+}
+
+impl SaveTo {
+    pub fn save_name(&self) -> &str {
+        self.save_name.as_deref().unwrap_or(DEFAULT_FILE_NAME)
+    }
+
+    pub fn apply(&mut self, profile: &Profile) {
+        self.save_dir = self.save_dir.take().or_else(|| profile.save_dir.clone());
+        self.save_name = self.save_name.take().or_else(|| profile.save_name.clone());
+    }
+}
+
+// Lists of file IDs, shared by every command taking file IDs.
+#[derive(Debug, Default, StructOpt)]
+pub struct FileLists {
+    /// Read file IDs or workshop URLs from a file, one per line, or from standard input if the path is `-`.
+    /// Blank lines and `#` comments are ignored, and mod orders saved with `--save` can be read back.
+    #[structopt(long, name = "list", number_of_values = 1)]
+    pub from_file: Vec<PathBuf>,
+}
+
+impl FileLists {
+    pub fn is_empty(&self) -> bool {
+        self.from_file.is_empty()
+    }
+
+    /// The file IDs given on the command line followed by those read from lists, without duplicates.
+    pub fn read(&self, files: Vec<FileArg>) -> Result<Vec<FileId>> {
+        let mut ids = Vec::new();
+        for file in files {
+            match file {
+                FileArg::Id(id) => ids.push(id),
+                FileArg::Stdin => ids.extend(read_file_ids_from(Path::new("-"))?),
+            }
+        }
+        for path in self.from_file.iter() {
+            ids.extend(read_file_ids_from(path).map_err(|e| {
+                e.wrap_err(format!("failed to read file IDs from {}", path.display()))
+            })?);
+        }
+        Ok(ids.into_iter().unique().collect())
+    }
+
+    /// Like [`FileLists::read`], but fail if no file IDs were given at all.
+    pub fn read_required(&self, files: Vec<FileArg>) -> Result<Vec<FileId>> {
+        let ids = self.read(files)?;
+        if ids.is_empty() {
+            return Err(color_eyre::eyre::eyre!("no file IDs given"));
+        }
+        Ok(ids)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Subcommand {
+    /// Download mods and collections, the same as `swd <files>`.
+    Download {
+        #[structopt(flatten)]
+        download: DownloadOpts,
+
+        /// File IDs or workshop URLs of the mods and collections to download, `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        files: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// Download only the items that were updated on the workshop since they were installed into the download location.
+    Update {
+        #[structopt(flatten)]
+        download: DownloadOpts,
+
+        /// File IDs or workshop URLs of the mods and collections to update, `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        files: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// Show the details of mods and collections without downloading anything.
+    Info {
+        /// Maximum depth of linked collections to follow, see the top-level `--max-depth`. [default: 8]
+        #[structopt(long)]
+        max_depth: Option<usize>,
+
+        /// File IDs or workshop URLs of the mods and collections to show, `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        files: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// Save the mod orders of collections to the current working directory without downloading anything.
+    SaveOrder {
+        /// Format of the saved mod orders, see the top-level `--save`. Defaults to the `save` of the config file, or csv.
+        #[structopt(long, possible_values(SaveFormat::NAMES))]
+        format: Option<SaveFormat>,
+
+        /// Review each mod one by one, see the top-level `--review`.
+        #[structopt(short, long)]
+        review: bool,
+
+        #[structopt(flatten)]
+        save_to: SaveTo,
+
+        /// Download location the `rimworld`, `zomboid` and `arma3` formats read mod IDs from, see the top-level `-o`.
+        /// Defaults to the `output` of the config file.
+        #[structopt(short, long, name = "path")]
+        output: Option<PathBuf>,
+
+        /// Maximum depth of linked collections to follow, see the top-level `--max-depth`. [default: 8]
+        #[structopt(long)]
+        max_depth: Option<usize>,
+
+        /// File IDs or workshop URLs of the collections, `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        files: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// List the workshop items swd installed into a download location.
+    List {
+        /// The download location, as passed to `-o` when installing.
+        #[structopt(short, long, name = "path")]
+        output: PathBuf,
+    },
+
+    /// Show everything recorded about an installed workshop item.
+    Inspect {
+        /// The download location, as passed to `-o` when installing.
+        #[structopt(short, long, name = "path")]
+        output: PathBuf,
+
+        /// File ID or workshop URL of the installed item.
+        #[structopt(parse(try_from_str = parse_file_id))]
+        file_id: FileId,
+    },
+
+    /// Resolve collections and record their exact contents and item versions in a lockfile.
+    Lock {
+        /// Path of the lockfile to write.
+        #[structopt(long, default_value = LOCKFILE_NAME, name = "lockfile")]
+        lockfile: PathBuf,
+
+        /// Maximum depth of linked collections to follow, see the top-level `--max-depth`. [default: 8]
+        #[structopt(long)]
+        max_depth: Option<usize>,
+
+        /// File IDs or workshop URLs of the mods and collections to lock, `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        files: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// Download the mods and collections recorded in a lockfile, warning about anything that changed since it was written.
+    Install {
+        #[structopt(flatten)]
+        download: DownloadOpts,
+
+        /// Path of the lockfile to install from.
+        #[structopt(long, default_value = LOCKFILE_NAME, name = "lockfile")]
+        lockfile: PathBuf,
+
+        /// Fail instead of warning when the collections or items changed since the lockfile was written.
+        #[structopt(long)]
+        locked: bool,
+    },
+
+    /// Remove installed workshop items that are no longer wanted from a download location.
+    /// Without any file IDs, only forgets items whose folder was already deleted.
+    Clean {
+        /// The download location, as passed to `-o` when installing.
+        #[structopt(short, long, name = "path")]
+        output: PathBuf,
+
+        /// Only print what would be removed.
+        #[structopt(long)]
+        dry_run: bool,
+
+        /// File IDs or workshop URLs of the mods and collections to keep, everything else swd installed is removed.
+        /// Linked collections are followed to any depth, so that nothing they hold is removed. `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        keep: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// Manage named sets of mods and collections in the workspace file.
+    Set {
+        /// Path of the workspace file.
+        #[structopt(long, default_value = WORKSPACE_NAME, name = "workspace")]
+        workspace: PathBuf,
+
+        #[structopt(subcommand)]
+        cmd: SetCommand,
+    },
+
+    /// Download every mod and collection in a named set of the workspace file.
+    Sync {
+        #[structopt(flatten)]
+        download: DownloadOpts,
+
+        /// Path of the workspace file.
+        #[structopt(long, default_value = WORKSPACE_NAME, name = "workspace")]
+        workspace: PathBuf,
+
+        /// Name of the set.
+        name: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum SetCommand {
+    /// Add mods and collections to a set, creating it if needed.
+    Add {
+        /// Name of the set.
+        name: String,
+
+        /// File IDs or workshop URLs of the mods and collections to add, `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        files: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// Remove mods and collections from a set, and the set once it is empty.
+    Remove {
+        /// Name of the set.
+        name: String,
+
+        /// File IDs or workshop URLs of the mods and collections to remove, `-` reads them from standard input.
+        #[structopt(parse(try_from_str = parse_file_arg))]
+        files: Vec<FileArg>,
+
+        #[structopt(flatten)]
+        lists: FileLists,
+    },
+
+    /// List the sets, or the contents of one set.
+    List {
+        /// Name of the set.
+        name: Option<String>,
+    },
+}
+
+fn info(max_depth: usize, files: &[FileId]) -> Result<()> {
+    let wfiles = WFiles::resolve(files, max_depth)?;
+    for file in files.iter().filter_map(|id| wfiles.get(id)) {
+        println!();
+        println!("File ID: \t {}", file.file_id);
+        println!("App ID: \t {}", file.app_id);
+        println!("Title: \t\t {}", file.title);
+        println!("Created at: \t {}", file.time_created.naive_local());
+        println!("Updated at: \t {}", file.time_updated.naive_local());
+        print_metadata(&file.metadata);
+
+        if file.is_collection() {
+            let mut mods = Vec::new();
+            wfiles.expand_collection(file, false, &mut HashSet::new(), &mut mods)?;
+            println!("Mods: \t\t {}", mods.len());
+            for (i, inner_file) in mods.iter().enumerate() {
+                println!(
+                    "    {} \t {} \t {}",
+                    i + 1,
+                    inner_file.file_id,
+                    inner_file.title
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_metadata(metadata: &Metadata) {
+    if let Some(creator) = metadata.creator.as_deref() {
+        println!("Creator: \t {}", creator);
+    }
+    if let Some(size) = metadata.size() {
+        println!("Size: \t\t {}", size);
+    }
+    if !metadata.tags.is_empty() {
+        println!("Tags: \t\t {}", metadata.tags.join(", "));
+    }
+    if let Some(subscriptions) = metadata.subscriptions {
+        println!("Subscribers: \t {}", subscriptions);
+    }
+    if let Some(favorited) = metadata.favorited {
+        println!("Favorited: \t {}", favorited);
+    }
+    if let (Some(up), Some(down)) = (metadata.votes_up, metadata.votes_down) {
+        println!("Votes: \t\t {} up, {} down", up, down);
+    }
+    if !metadata.required_items.is_empty() {
+        println!("Requires: \t {}", metadata.required_items.iter().join(", "));
+    }
+    if let Some(visibility) = metadata.visibility_name() {
+        println!("Visibility: \t {}", visibility);
+    }
+    if let Some(banned) = metadata.banned {
+        println!("Banned: \t {}", if banned { "yes" } else { "no" });
+    }
+    if let Some(url) = metadata.preview_url.as_deref() {
+        println!("Preview: \t {}", url);
+    }
+    if let Some(url) = metadata.file_url.as_deref() {
+        println!("File URL: \t {}", url);
+    }
+}
+
+fn save_order(
+    format: SaveFormat,
+    review: bool,
+    save_to: SaveTo,
+    output: Option<PathBuf>,
+    max_depth: Option<usize>,
+    files: Vec<FileId>,
+    profile: &Profile,
+) -> Result<()> {
+    let mut opts = DownloadOpts {
+        review,
+        save: Some(format),
+        save_to,
+        output,
+        max_depth,
+        ..Default::default()
+    };
+    opts.apply(profile);
+    let app = App::new(opts, files)?;
+    app.save_orders(&app.build_plan()?)
+}
+
+fn list(output: &Path) -> Result<()> {
+    let manifest = Manifest::load(output)?;
+    if manifest.items.is_empty() {
+        println!("No workshop items recorded in {}", output.display());
+    }
+    for item in manifest.items.iter() {
+        println!(
+            "{} \t {} \t {} \t\t Updated at: \t {}",
+            item.app_id,
+            item.file_id,
+            item.title,
+            item.time_updated.naive_local()
+        );
+    }
+    Ok(())
+}
+
+fn inspect(output: &Path, file_id: FileId) -> Result<()> {
+    let manifest = Manifest::load(output)?;
+    let item = manifest.get(file_id).ok_or_else(|| {
+        color_eyre::eyre::eyre!("{} is not recorded in {}", file_id, output.display())
+    })?;
+
+    println!("File ID: \t {}", item.file_id);
+    println!("App ID: \t {}", item.app_id);
+    println!("Title: \t\t {}", item.title);
+    println!("Updated at: \t {}", item.time_updated.naive_local());
+    match item.collection {
+        Some(collection) => println!("Collection: \t {}", collection),
+        None => println!("Collection: \t -"),
+    }
+    println!("Path: \t\t {}", item.path.display());
+    println!("Installed at: \t {}", item.installed_at.naive_local());
+    Ok(())
+}
+
+fn lock(lockfile: &Path, max_depth: usize, files: &[FileId]) -> Result<()> {
+    let wfiles = WFiles::resolve(files, max_depth)?;
+    let lock = Lock::new(files, &wfiles);
+    lock.save(lockfile)?;
+    println!(
+        "Locked {} items in {}",
+        lock.items.len(),
+        lockfile.display()
+    );
+    Ok(())
+}
+
+fn install(download: DownloadOpts, lockfile: &Path, locked: bool) -> Result<()> {
+    let lock = Lock::load(lockfile)?;
+    let wfiles = WFiles::resolve(&lock.roots, download.max_depth())?;
+
+    let divergences = lock.diff(&wfiles);
+    if !divergences.is_empty() {
+        println!("{} changed since it was written:", lockfile.display());
+        for divergence in divergences.iter() {
+            println!("    {}", divergence);
+        }
+        if locked {
+            return Err(color_eyre::eyre::eyre!(
+                "{} differences from {}",
+                divergences.len(),
+                lockfile.display()
+            ));
+        }
+    }
+
+    // download what was locked, not what the collections hold now
+    let wfiles = lock.wfiles(&wfiles)?;
+    App::with_wfiles(download, lock.roots, wfiles)?.run()
+}
+
+fn clean(output: &Path, dry_run: bool, keep: &[FileId]) -> Result<()> {
+    let keep = if keep.is_empty() {
+        None
+    } else {
+        // a depth limit would leave the items of deeper collections looking unwanted
+        let wfiles = WFiles::resolve(keep, usize::MAX)?;
+        // the contents of a collection that could not be retrieved are unknown, so nothing can be safely removed
+        wfiles
+            .ensure_valid(keep)
+            .wrap_err("not removing anything, as the items to keep could not all be retrieved")?;
+        Some(wfiles)
+    };
+
+    let mut manifest = Manifest::load(output)?;
+    let mut removed = Vec::new();
+    for item in manifest.items.iter() {
+        let wanted = match keep.as_ref() {
+            // items the API did not return are left alone rather than assumed unwanted
+            Some(keep) => keep.contains(&item.file_id) || keep.invalid.contains_key(&item.file_id),
+            None => item.path.exists(),
+        };
+        if wanted {
+            continue;
+        }
+
+        if dry_run {
+            println!("Would remove \t {}: \t {}", item.file_id, item.title);
+            continue;
+        }
+        if item.path.exists() {
+            fs::remove_dir_all(&item.path)?;
+        }
+        println!("Removed \t {}: \t {}", item.file_id, item.title);
+        removed.push(item.file_id);
+    }
+
+    if !removed.is_empty() {
+        manifest
+            .items
+            .retain(|item| !removed.contains(&item.file_id));
+        manifest.save(output)?;
+    }
+    Ok(())
+}
+
+fn set(workspace_path: &Path, cmd: SetCommand) -> Result<()> {
+    let mut workspace = Workspace::load(workspace_path)?;
+    match cmd {
+        SetCommand::Add { name, files, lists } => {
+            let files = lists.read_required(files)?;
+            let wfiles = WFiles::resolve(&files, 0)?;
+            let valid = files
+                .into_iter()
+                .filter(|id| wfiles.contains(id))
+                .collect::<Vec<_>>();
+            for file_id in workspace.add(&name, &valid) {
+                println!(
+                    "Added \t {}: \t {}",
+                    file_id, wfiles.all_files[&file_id].title
+                );
+            }
+        }
+        SetCommand::Remove { name, files, lists } => {
+            let files = lists.read_required(files)?;
+            for file_id in workspace.remove(&name, &files)? {
+                println!("Removed \t {}", file_id);
+            }
+        }
+        SetCommand::List { name: None } => {
+            for (name, set) in workspace.sets.iter() {
+                println!("{} \t {} items", name, set.files.len());
+            }
+            return Ok(());
+        }
+        SetCommand::List { name: Some(name) } => {
+            let set = workspace.get(&name)?;
+            let wfiles = WFiles::resolve(&set.files, 0)?;
+            for file_id in set.files.iter() {
+                let title = wfiles.get(file_id).map_or("", |file| file.title.as_str());
+                println!("{} \t {}", file_id, title);
+            }
+            return Ok(());
+        }
+    }
+    workspace.save(workspace_path)
+}
+
+/// Entry point of the `swd` binary.
+pub fn run(params: Params) -> Result<()> {
+    let config = Config::find(params.config.as_deref())?;
+    run_with_config(params, &config)
+}
+
+/// Run with `config` in place of the config file given with `--config` or found at the default path.
+pub fn run_with_config(params: Params, config: &Config) -> Result<()> {
+    let profile = config.profile(params.profile.as_deref())?;
+
+    if let Some(url) = params.api_url.as_deref().or(profile.api_url.as_deref()) {
+        net::set_base_url(url)?;
+    }
+    if let Some(batch_size) = params.api_batch_size.or(profile.api_batch_size) {
+        net::set_batch_size(batch_size);
+    }
+    if let Some(concurrency) = params.api_concurrency.or(profile.api_concurrency) {
+        net::set_concurrency(concurrency);
+    }
+    if let Some(key) = params.api_key.or(profile.api_key.clone()) {
+        net::set_api_key(key)?;
+    }
+    if let Some(backend) = params.api_backend.or(profile.api_backend) {
+        net::set_backend(backend)?;
+    }
+    if let Some(timeout) = params.api_timeout.or(profile.api_timeout) {
+        net::set_timeout(Duration::from_secs(timeout));
+    }
+    if let Some(retries) = params.api_retries.or(profile.api_retries) {
+        net::set_retries(retries);
+    }
+
+    if let Some(cmd) = params.cmd {
+        // the top-level download options and files only apply without a subcommand
+        if params.download != DownloadOpts::default() {
+            return Err(color_eyre::eyre::eyre!(
+                "download options such as -e, -u and -o go after the subcommand, e.g. swd download -e <files>"
+            ));
+        }
+        if !params.files.is_empty() || !params.lists.is_empty() {
+            return Err(color_eyre::eyre::eyre!(
+                "file IDs go after the subcommand, e.g. swd info <files>"
+            ));
+        }
+
+        match cmd {
+            Subcommand::Download {
+                mut download,
+                files,
+                lists,
+            } => {
+                download.apply(&profile);
+                App::new(download, lists.read_required(files)?)?.run()?
+            }
+            Subcommand::Update {
+                mut download,
+                files,
+                lists,
+            } => {
+                download.apply(&profile);
+                download.update = true;
+                App::new(download, lists.read_required(files)?)?.run()?
+            }
+            Subcommand::Info {
+                max_depth,
+                files,
+                lists,
+            } => info(
+                self::max_depth(max_depth, &profile),
+                &lists.read_required(files)?,
+            )?,
+            Subcommand::SaveOrder {
+                format,
+                review,
+                save_to,
+                output,
+                max_depth,
+                files,
+                lists,
+            } => save_order(
+                format.or(profile.save).unwrap_or(SaveFormat::Csv),
+                review,
+                save_to,
+                output,
+                max_depth,
+                lists.read_required(files)?,
+                &profile,
+            )?,
+            Subcommand::List { output } => list(&output)?,
+            Subcommand::Inspect { output, file_id } => inspect(&output, file_id)?,
+            Subcommand::Lock {
+                lockfile,
+                max_depth,
+                files,
+                lists,
+            } => lock(
+                &lockfile,
+                self::max_depth(max_depth, &profile),
+                &lists.read_required(files)?,
+            )?,
+            Subcommand::Install {
+                mut download,
+                lockfile,
+                locked,
+            } => {
+                download.apply(&profile);
+                install(download, &lockfile, locked)?
+            }
+            Subcommand::Clean {
+                output,
+                dry_run,
+                keep,
+                lists,
+            } => clean(&output, dry_run, &lists.read(keep)?)?,
+            Subcommand::Set { workspace, cmd } => set(&workspace, cmd)?,
+            Subcommand::Sync {
+                mut download,
+                workspace,
+                name,
+            } => {
+                download.apply(&profile);
+                let files = Workspace::load(&workspace)?.get(&name)?.files.clone();
+                App::new(download, files)?.run()?
+            }
+        }
+    } else if params.files.is_empty() && params.lists.is_empty() {
+        Params::clap().print_long_help()?;
+    } else {
+        let files = params.lists.read(params.files)?;
+        let mut download = params.download;
+        download.apply(&profile);
+        let app = App::new(download, files)?;
+        app.run()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::mock;
+    use crate::util::TempPath;
+
+    /// [`super::run`] without the config file of whoever runs the tests.
+    fn run(params: Params) -> Result<()> {
+        run_with_config(params, &Config::default())
+    }
+
+    #[test]
+    fn test_null() -> Result<()> {
+        let params = Params::from_iter(["swd"]);
+        run(params)
+    }
+
+    #[test]
+    fn test_subcommands() -> Result<()> {
+        let params = Params::from_iter(["swd", "inspect", "-o", "/srv/mods", "818773962"]);
+        assert!(params.files.is_empty());
+        assert!(matches!(
+            params.cmd,
+            Some(Subcommand::Inspect {
+                file_id: 818773962,
+                ..
+            })
+        ));
+
+        let dir = TempPath::new("subcommands");
+        let params = Params::from_iter([
+            "swd".as_ref(),
+            "list".as_ref(),
+            "-o".as_ref(),
+            dir.as_os_str(),
+        ]);
+        run(params)?;
+        let params = Params::from_iter([
+            "swd".as_ref(),
+            "inspect".as_ref(),
+            "-o".as_ref(),
+            dir.as_os_str(),
+            "818773962".as_ref(),
+        ]);
+        assert!(run(params).is_err());
+
+        // download options before a subcommand would be ignored
+        let params = Params::from_iter([
+            "swd".as_ref(),
+            "-e".as_ref(),
+            "-u".as_ref(),
+            "someone".as_ref(),
+            "list".as_ref(),
+            "-o".as_ref(),
+            dir.as_os_str(),
+        ]);
+        assert!(run(params).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_urls() {
+        let params = Params::from_iter([
+            "swd",
+            "https://steamcommunity.com/sharedfiles/filedetails/?id=368330611",
+            "steam://url/CommunityFilePage/2824342092",
+            "1626860092",
+        ]);
+        assert_eq!(
+            params.lists.read(params.files).unwrap(),
+            [368330611, 2824342092, 1626860092]
+        );
+
+        let params = Params::from_iter_safe(["swd", "https://example.com/?id=368330611"]);
+        assert!(params.is_err());
+    }
+
+    #[test]
+    fn test_from_file() -> Result<()> {
+        mock::serve();
+        let path = TempPath::new("from_file.txt");
+        fs::write(&path, "# mods\n2824342092\n368330611 # Essential Mods\n")?;
+
+        let params = Params::from_iter([
+            "swd".as_ref(),
+            "-".as_ref(),
+            "--from-file".as_ref(),
+            path.as_os_str(),
+        ]);
+        assert_eq!(params.files, [FileArg::Stdin]);
+        let params = Params::from_iter([
+            "swd".as_ref(),
+            "1626860092".as_ref(),
+            "--from-file".as_ref(),
+            path.as_os_str(),
+            "368330611".as_ref(),
+        ]);
+        assert_eq!(
+            params.lists.read(params.files)?,
+            [1626860092, 368330611, 2824342092]
+        );
+
+        run(Params::from_iter([
+            "swd".as_ref(),
+            "info".as_ref(),
+            "--from-file".as_ref(),
+            path.as_os_str(),
+        ]))?;
+        fs::remove_file(&path)?;
+        assert!(run(Params::from_iter(["swd", "info"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config() -> Result<()> {
+        let path = TempPath::new("cli_config.toml");
+        fs::write(
+            &path,
+            "username = \"someone\"\nretries = 2\n\n[profile.rimworld]\noutput = \"/srv/rimworld\"\nsave = \"rimworld\"\n",
+        )?;
+        let params = Params::from_iter([
+            "swd".as_ref(),
+            "download".as_ref(),
+            "-u".as_ref(),
+            "anonymous".as_ref(),
+            "--config".as_ref(),
+            path.as_os_str(),
+            "--profile".as_ref(),
+            "rimworld".as_ref(),
+            "368330611".as_ref(),
+        ]);
+        let profile = Config::find(params.config.as_deref())?.profile(params.profile.as_deref())?;
+        fs::remove_file(&path)?;
+
+        let Some(Subcommand::Download { mut download, .. }) = params.cmd else {
+            panic!("expected download subcommand");
+        };
+        download.apply(&profile);
+        assert_eq!(download.username(), "anonymous");
+        assert_eq!(download.output.as_deref(), Some(Path::new("/srv/rimworld")));
+        assert_eq!(download.save, Some(SaveFormat::RimWorld));
+        assert_eq!(download.retries(), 2);
+        assert_eq!(download.max_depth(), 8);
+
+        let profile = Profile {
+            exec: Some(true),
+            strict: Some(true),
+            ..Default::default()
+        };
+        let mut download = DownloadOpts::from_iter(["swd", "--no-exec", "--no-strict"]);
+        download.apply(&profile);
+        assert!(!download.exec && !download.strict);
+        let mut download = DownloadOpts::from_iter(["swd", "--no-exec", "-e"]);
+        download.apply(&Profile::default());
+        assert!(download.exec);
+        let mut download = DownloadOpts::default();
+        download.apply(&profile);
+        assert!(download.exec && download.strict);
+
+        let profile = Profile {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(max_depth(None, &profile), 2);
+        assert_eq!(max_depth(Some(0), &profile), 0);
+        assert_eq!(max_depth(None, &Profile::default()), DEFAULT_MAX_DEPTH);
+
+        assert!(Params::from_iter_safe(["swd", "--output-for", "rimworld=/srv/rimworld"]).is_err());
+        assert!(Params::from_iter_safe(["swd", "--output-for", "294100"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_sync() -> Result<()> {
+        mock::serve();
+        let path = TempPath::new("cli_workspace.toml");
+        let args = |args: &[&str]| {
+            let mut all = vec!["swd".as_ref()];
+            all.extend(args.iter().map(std::ffi::OsStr::new));
+            all.splice(2..2, ["--workspace".as_ref(), path.as_os_str()]);
+            Params::from_iter(all)
+        };
+
+        run(args(&["set", "add", "survival", "368330611", "1111"]))?;
+        run(args(&["set", "add", "survival", "2824342092"]))?;
+        run(args(&["set", "add", "creative", "1626860092"]))?;
+        run(args(&["set", "remove", "creative", "1626860092"]))?;
+        run(args(&["set", "list", "survival"]))?;
+        run(args(&["sync", "survival"]))?;
+        assert!(run(args(&["sync", "creative"])).is_err());
+
+        let workspace = Workspace::load(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(workspace.sets.len(), 1);
+        assert_eq!(workspace.get("survival")?.files, [368330611, 2824342092]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_backend() {
+        let params = Params::from_iter([
+            "swd",
+            "--api-key",
+            "0123456789ABCDEF",
+            "--api-backend",
+            "published-file-service",
+            "368330611",
+        ]);
+        assert_eq!(params.api_key.as_deref(), Some("0123456789ABCDEF"));
+        assert_eq!(params.api_backend, Some(net::Backend::PublishedFileService));
+        assert!(Params::from_iter_safe(["swd", "--api-backend", "web", "368330611"]).is_err());
+
+        // the API options are also accepted after a subcommand
+        let params = Params::from_iter([
+            "swd",
+            "info",
+            "--api-url",
+            "http://localhost:8080/",
+            "--api-batch-size",
+            "10",
+            "--api-retries",
+            "0",
+            "368330611",
+        ]);
+        assert_eq!(params.api_url.as_deref(), Some("http://localhost:8080/"));
+        assert_eq!(params.api_batch_size, Some(10));
+        assert_eq!(params.api_retries, Some(0));
+    }
+
+    #[test]
+    fn test_download_alias() {
+        let bare = Params::from_iter(["swd", "-e", "-o", "/srv/mods", "368330611"]);
+        let sub = Params::from_iter(["swd", "download", "-e", "-o", "/srv/mods", "368330611"]);
+
+        assert_eq!(bare.files, [FileArg::Id(368330611)]);
+        assert!(bare.download.exec);
+        match sub.cmd {
+            Some(Subcommand::Download {
+                download, files, ..
+            }) => {
+                assert_eq!(files, bare.files);
+                assert!(download.exec);
+                assert_eq!(download.output, bare.download.output);
+            }
+            _ => panic!("expected download subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_info_save_order() -> Result<()> {
+        mock::serve();
+        run(Params::from_iter([
+            "swd",
+            "info",
+            "368330611",
+            "2824342092",
+        ]))?;
+
+        let dir = TempPath::new("save_dir");
+        run(Params::from_iter([
+            "swd".as_ref(),
+            "save-order".as_ref(),
+            "--format".as_ref(),
+            "simple".as_ref(),
+            "--save-dir".as_ref(),
+            dir.as_os_str(),
+            "1626860092".as_ref(),
+        ]))?;
+        let saved = fs::read_to_string(dir.join("1626860092 Small Collection.csv"))?;
+        assert_eq!(saved, "2529002857\n2824342092\n");
+
+        run(Params::from_iter([
+            "swd".as_ref(),
+            "save-order".as_ref(),
+            "--format=json".as_ref(),
+            "--save-dir".as_ref(),
+            dir.as_os_str(),
+            "--save-name".as_ref(),
+            "{title} ({updated})".as_ref(),
+            "1626860092".as_ref(),
+        ]))?;
+        let saved = dir.join("Small Collection (2020-09-13).json");
+        assert!(saved.exists());
+
+        // game formats read mod IDs from the download location given with -o
+        let about = crate::local::item_dir(&dir, 294100, 2824342092).join("About");
+        fs::create_dir_all(&about)?;
+        fs::write(
+            about.join("About.xml"),
+            "<ModMetaData><packageId>Taranchuk.PerformanceFish</packageId></ModMetaData>",
+        )?;
+        run(Params::from_iter([
+            "swd".as_ref(),
+            "save-order".as_ref(),
+            "--format=rimworld".as_ref(),
+            "--save-dir".as_ref(),
+            dir.as_os_str(),
+            "-o".as_ref(),
+            dir.as_os_str(),
+            "1626860092".as_ref(),
+        ]))?;
+        let saved = fs::read_to_string(dir.join("1626860092 Small Collection.xml"))?;
+        assert!(saved.contains("<li>taranchuk.performancefish</li>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_clean() -> Result<()> {
+        mock::serve();
+        let dir = TempPath::new("clean");
+
+        let mut manifest = Manifest::default();
+        for (file_id, title) in [
+            (2529002857, "Dubs Mint Menus"),
+            (818773962, "HugsLib"),
+            (761421485, "Allow Tool"),
+            (1541438856, "Numbers"),
+        ] {
+            let path = crate::local::item_dir(&dir, 294100, file_id);
+            if file_id != 761421485 {
+                fs::create_dir_all(&path)?;
+            }
+            manifest.insert(crate::manifest::ManifestItem {
+                file_id,
+                app_id: 294100,
+                title: title.into(),
+                time_updated: chrono::Utc::now(),
+                collection: None,
+                path,
+                installed_at: chrono::Utc::now(),
+            });
+        }
+        manifest.save(&dir)?;
+
+        let clean = |args: &[&str]| {
+            let mut all = vec![
+                "swd".as_ref(),
+                "clean".as_ref(),
+                "-o".as_ref(),
+                dir.as_os_str(),
+            ];
+            all.extend(args.iter().map(std::ffi::OsStr::new));
+            run(Params::from_iter(all))
+        };
+
+        // forget 761421485, whose folder is gone
+        clean(&[])?;
+        let ids = |dir: &Path| -> Result<Vec<FileId>> {
+            Ok(Manifest::load(dir)?
+                .items
+                .iter()
+                .map(|i| i.file_id)
+                .collect())
+        };
+        assert_eq!(ids(&dir)?, [818773962, 1541438856, 2529002857]);
+
+        // nothing is removed when an item to keep is invalid
+        assert!(clean(&["1626860092", "1111"]).is_err());
+        assert_eq!(ids(&dir)?, [818773962, 1541438856, 2529002857]);
+
+        // the mods of linked collections are kept, and no depth limit can be set
+        clean(&["368330611", "2529002857"])?;
+        assert_eq!(ids(&dir)?, [818773962, 1541438856, 2529002857]);
+        assert!(Params::from_iter_safe(["swd", "clean", "-o", "dir", "--max-depth", "0"]).is_err());
+
+        // keep only the mods of 1626860092
+        clean(&["--dry-run", "1626860092"])?;
+        assert_eq!(ids(&dir)?, [818773962, 1541438856, 2529002857]);
+        clean(&["1626860092"])?;
+        assert_eq!(ids(&dir)?, [2529002857]);
+        assert!(!crate::local::item_dir(&dir, 294100, 818773962).exists());
+        assert!(crate::local::item_dir(&dir, 294100, 2529002857).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_install() -> Result<()> {
+        mock::serve();
+        let path = TempPath::new("cli.lock");
+        let args = |args: &[&str]| {
+            let mut all = vec!["swd".as_ref()];
+            all.extend(args.iter().map(std::ffi::OsStr::new));
+            all.splice(2..2, ["--lockfile".as_ref(), path.as_os_str()]);
+            Params::from_iter(all)
+        };
+
+        run(args(&["lock", "368330611"]))?;
+        run(args(&["install", "--locked"]))?;
+
+        let mut lock = Lock::load(&path)?;
+        lock.collections[0].children.pop();
+        lock.save(&path)?;
+        run(args(&["install"]))?;
+        let locked = run(args(&["install", "--locked"]));
+        std::fs::remove_file(&path)?;
+
+        assert!(locked.is_err());
+        Ok(())
+    }
+
+    // #[test]
+    // fn test_collection_review() -> Result<()> {
+    //     let params =
+    //         Params::from_iter(["swd", "-r", "--save=csv", "368330611", "116676096"]);
+    //     run(params)
+    // }
+}
@@ -0,0 +1,233 @@
+//! The configuration file, holding defaults for command-line options and named profiles of them.
+//!
+//! ```toml
+//! username = "someone"
+//! save = "csv"
+//!
+//! [profile.rimworld]
+//! output = "/srv/rimworld"
+//! save = "rimworld"
+//! ```
+//!
+//! Values at the top level apply to every run, those of the profile selected with `--profile` take precedence over them,
+//! and options given on the command line take precedence over both.
+
+use crate::{export::SaveFormat, net::Backend, prelude::*};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+pub const CONFIG_NAME: &str = "config.toml";
+
+/// Defaults for command-line options. Every field corresponds to the option of the same name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub exec: Option<bool>,
+    pub username: Option<String>,
+    pub output: Option<PathBuf>,
+    /// Download locations by app id, as set with `--output-for`.
+    #[serde(default, deserialize_with = "deserialize_app_dirs")]
+    pub output_for: Option<BTreeMap<FileId, PathBuf>>,
+    pub save: Option<SaveFormat>,
+    pub save_dir: Option<PathBuf>,
+    pub save_name: Option<String>,
+    pub retries: Option<u32>,
+    pub retry_delay: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub strict: Option<bool>,
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+    pub api_backend: Option<Backend>,
+    pub api_batch_size: Option<usize>,
+    pub api_concurrency: Option<usize>,
+    pub api_timeout: Option<u64>,
+    pub api_retries: Option<u32>,
+}
+
+impl Profile {
+    /// Fill in the values `self` leaves unset from `defaults`.
+    pub fn or(self, defaults: &Profile) -> Profile {
+        let defaults = defaults.clone();
+        Profile {
+            exec: self.exec.or(defaults.exec),
+            username: self.username.or(defaults.username),
+            output: self.output.or(defaults.output),
+            output_for: match (self.output_for, defaults.output_for) {
+                (Some(mut dirs), Some(defaults)) => {
+                    for (app_id, path) in defaults {
+                        dirs.entry(app_id).or_insert(path);
+                    }
+                    Some(dirs)
+                }
+                (dirs, defaults) => dirs.or(defaults),
+            },
+            save: self.save.or(defaults.save),
+            save_dir: self.save_dir.or(defaults.save_dir),
+            save_name: self.save_name.or(defaults.save_name),
+            retries: self.retries.or(defaults.retries),
+            retry_delay: self.retry_delay.or(defaults.retry_delay),
+            max_depth: self.max_depth.or(defaults.max_depth),
+            strict: self.strict.or(defaults.strict),
+            api_url: self.api_url.or(defaults.api_url),
+            api_key: self.api_key.or(defaults.api_key),
+            api_backend: self.api_backend.or(defaults.api_backend),
+            api_batch_size: self.api_batch_size.or(defaults.api_batch_size),
+            api_concurrency: self.api_concurrency.or(defaults.api_concurrency),
+            api_timeout: self.api_timeout.or(defaults.api_timeout),
+            api_retries: self.api_retries.or(defaults.api_retries),
+        }
+    }
+}
+
+/// TOML keys are always strings, so app ids are parsed from them by hand.
+fn deserialize_app_dirs<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<BTreeMap<FileId, PathBuf>>, D::Error> {
+    let dirs = BTreeMap::<String, PathBuf>::deserialize(deserializer)?;
+    dirs.into_iter()
+        .map(|(app_id, path)| match app_id.parse() {
+            Ok(app_id) => Ok((app_id, path)),
+            Err(_) => Err(serde::de::Error::custom(format!(
+                "invalid app id '{}'",
+                app_id
+            ))),
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub defaults: Profile,
+    pub profile: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/swd/config.toml`, falling back to `~/.config/swd/config.toml`, or `%APPDATA%\swd\config.toml` on Windows.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else {
+            env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        };
+        Some(dir?.join("swd").join(CONFIG_NAME))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        // split off the profiles by hand, as unknown keys cannot be rejected through #[serde(flatten)]
+        let mut table: toml::value::Table = toml::from_str(text)?;
+        let profile = match table.remove("profile") {
+            Some(profile) => profile.try_into()?,
+            None => BTreeMap::new(),
+        };
+        let defaults = toml::Value::Table(table).try_into()?;
+        Ok(Self { defaults, profile })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Load the config file given with `--config`, or the one at the default path if there is one.
+    pub fn find(path: Option<&Path>) -> Result<Self> {
+        let result = match path {
+            Some(path) => Self::load(path).map_err(|e| (e, path.to_path_buf())),
+            None => match Self::default_path().filter(|path| path.exists()) {
+                Some(path) => Self::load(&path).map_err(|e| (e, path)),
+                None => Ok(Self::default()),
+            },
+        };
+        result.map_err(|(e, path)| e.wrap_err(format!("failed to read {}", path.display())))
+    }
+
+    /// The top-level defaults, overridden by the named profile if there is one.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        let Some(name) = name else {
+            return Ok(self.defaults.clone());
+        };
+        let profile = self.profile.get(name).ok_or_else(|| {
+            color_eyre::eyre::eyre!(
+                "no profile named '{}' in the config file, the profiles are: {}",
+                name,
+                self.profile.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        Ok(profile.clone().or(&self.defaults))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempPath;
+
+    const CONFIG: &str = r#"
+username = "someone"
+save = "csv"
+retries = 2
+api_timeout = 60
+
+[profile.rimworld]
+output = "/srv/rimworld"
+save = "rimworld"
+
+[profile.zomboid]
+username = "admin"
+api_backend = "published-file-service"
+api_retries = 5
+
+[profile.zomboid.output_for]
+108600 = "/srv/zomboid"
+"#;
+
+    #[test]
+    fn test_profile() -> Result<()> {
+        let config = Config::parse(CONFIG)?;
+
+        let defaults = config.profile(None)?;
+        assert_eq!(defaults.username.as_deref(), Some("someone"));
+        assert_eq!(defaults.output, None);
+
+        let rimworld = config.profile(Some("rimworld"))?;
+        assert_eq!(rimworld.save, Some(SaveFormat::RimWorld));
+        assert_eq!(rimworld.output, Some(PathBuf::from("/srv/rimworld")));
+        assert_eq!(rimworld.username.as_deref(), Some("someone"));
+        assert_eq!(rimworld.retries, Some(2));
+
+        let zomboid = config.profile(Some("zomboid"))?;
+        assert_eq!(zomboid.username.as_deref(), Some("admin"));
+        assert_eq!(zomboid.save, Some(SaveFormat::Csv));
+        assert_eq!(zomboid.api_backend, Some(Backend::PublishedFileService));
+        assert_eq!(zomboid.api_retries, Some(5));
+        assert_eq!(zomboid.api_timeout, Some(60));
+        assert_eq!(
+            zomboid.output_for,
+            Some(BTreeMap::from([(108600, PathBuf::from("/srv/zomboid"))]))
+        );
+
+        assert!(config.profile(Some("arma3")).is_err());
+        assert!(Config::parse("usrname = \"typo\"").is_err());
+        assert!(Config::parse("[profile.arma3]\nsave = \"pbo\"").is_err());
+        assert!(Config::parse("[output_for]\narma3 = \"/srv/arma3\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find() -> Result<()> {
+        let path = TempPath::new("config.toml");
+        fs::write(&path, CONFIG)?;
+        let config = Config::find(Some(&path));
+        fs::remove_file(&path)?;
+
+        assert_eq!(config?.profile.len(), 2);
+        assert!(Config::find(Some(&path)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Load-order formats of individual games.
+//!
+//! RimWorld and Project Zomboid refer to mods by IDs declared inside the mod files rather than by workshop file ID,
+//! so those are read from the installed items when a download location is known. Mods that are not installed yet
+//! fall back to their file ID and are listed in a comment, as the exported order needs fixing up by hand for them.
+
+use crate::{local::item_dir, workshop::WFile};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Package ID from `About/About.xml` of an installed RimWorld mod, lowercased as RimWorld does.
+fn rimworld_package_id(dir: &Path) -> Option<String> {
+    let about = fs::read_to_string(dir.join("About").join("About.xml")).ok()?;
+    let start = about.find("<packageId>")? + "<packageId>".len();
+    let end = start + about[start..].find("</packageId>")?;
+    Some(about[start..end].trim().to_lowercase())
+}
+
+/// Mod IDs from the `mods/<name>/mod.info` files of an installed Project Zomboid workshop item, which can hold several mods.
+fn zomboid_mod_ids(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir.join("mods")) else {
+        return Vec::new();
+    };
+    let mut mods = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    mods.sort();
+
+    mods.iter()
+        .filter_map(|dir| fs::read_to_string(dir.join("mod.info")).ok())
+        .filter_map(|info| {
+            info.lines()
+                .find_map(|line| line.trim().strip_prefix("id="))
+                .map(|id| id.trim().to_string())
+        })
+        .collect()
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Package ID of the RimWorld base game, which is listed first in `activeMods`.
+const RIMWORLD_CORE: &str = "ludeon.rimworld";
+
+/// The newest RimWorld version among the workshop tags of `mods`, which tag the versions they support as e.g. `1.4`.
+fn rimworld_version(mods: &[&WFile]) -> Option<String> {
+    mods.iter()
+        .flat_map(|file| file.metadata.tags.iter())
+        .filter_map(|tag| {
+            let (major, minor) = tag.split_once('.')?;
+            Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?))
+        })
+        .max()
+        .map(|(major, minor)| format!("{}.{}", major, minor))
+}
+
+/// RimWorld's `ModsConfig.xml`, with the base game followed by `mods`. The version is the newest one the mods are
+/// tagged with, and `knownExpansions` is left empty so the game enables the owned expansions itself on the next start.
+pub fn write_rimworld(
+    w: &mut impl Write,
+    install_dir: Option<&Path>,
+    mods: &[&WFile],
+) -> io::Result<()> {
+    let mut missing = Vec::new();
+    let ids = mods
+        .iter()
+        .map(|file| {
+            install_dir
+                .and_then(|dir| rimworld_package_id(&item_dir(dir, file.app_id, file.file_id)))
+                .unwrap_or_else(|| {
+                    missing.push(*file);
+                    file.file_id.to_string()
+                })
+        })
+        .collect::<Vec<_>>();
+
+    writeln!(w, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(w, "<ModsConfigData>")?;
+    if let Some(version) = rimworld_version(mods) {
+        writeln!(w, "  <version>{}</version>", version)?;
+    }
+    if !missing.is_empty() {
+        writeln!(
+            w,
+            "  <!-- not installed, listed by file ID instead of package ID: {} -->",
+            missing
+                .iter()
+                .map(|file| file.file_id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+    }
+    writeln!(w, "  <activeMods>")?;
+    writeln!(w, "    <li>{}</li>", RIMWORLD_CORE)?;
+    for id in ids.iter().filter(|id| *id != RIMWORLD_CORE) {
+        writeln!(w, "    <li>{}</li>", xml_escape(id))?;
+    }
+    writeln!(w, "  </activeMods>")?;
+    writeln!(w, "  <knownExpansions />")?;
+    writeln!(w, "</ModsConfigData>")?;
+    Ok(())
+}
+
+/// The `Mods=` and `WorkshopItems=` lines of a Project Zomboid server ini.
+pub fn write_zomboid(
+    w: &mut impl Write,
+    install_dir: Option<&Path>,
+    mods: &[&WFile],
+) -> io::Result<()> {
+    let mut mod_ids = Vec::new();
+    let mut missing = Vec::new();
+    for file in mods {
+        let ids = install_dir
+            .map(|dir| zomboid_mod_ids(&item_dir(dir, file.app_id, file.file_id)))
+            .unwrap_or_default();
+        if ids.is_empty() {
+            missing.push(file.file_id.to_string());
+        }
+        mod_ids.extend(ids);
+    }
+
+    if !missing.is_empty() {
+        writeln!(
+            w,
+            "# not installed, add the mod IDs of these workshop items to Mods= by hand: {}",
+            missing.join(", ")
+        )?;
+    }
+    writeln!(w, "Mods={}", mod_ids.join(";"))?;
+    writeln!(
+        w,
+        "WorkshopItems={}",
+        mods.iter()
+            .map(|file| file.file_id.to_string())
+            .collect::<Vec<_>>()
+            .join(";")
+    )?;
+    Ok(())
+}
+
+/// An Arma 3 `-mod=` launch parameter with the folder of every mod, relative to the SteamCMD directory without a download location.
+pub fn write_arma3(
+    w: &mut impl Write,
+    install_dir: Option<&Path>,
+    mods: &[&WFile],
+) -> io::Result<()> {
+    let install_dir = install_dir.unwrap_or_else(|| Path::new(""));
+    let paths = mods
+        .iter()
+        .map(|file| {
+            item_dir(install_dir, file.app_id, file.file_id)
+                .display()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(";");
+
+    if paths.contains(' ') {
+        writeln!(w, "\"-mod={}\"", paths)
+    } else {
+        writeln!(w, "-mod={}", paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::util::TempPath;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    fn file(app_id: FileId, file_id: FileId) -> WFile {
+        WFile {
+            file_id,
+            app_id,
+            children: None,
+            sortorders: HashMap::new(),
+            title: String::new(),
+            description: String::new(),
+            time_created: Utc::now(),
+            time_updated: Utc::now(),
+            metadata: Default::default(),
+        }
+    }
+
+    type Exporter = fn(&mut Vec<u8>, Option<&Path>, &[&WFile]) -> io::Result<()>;
+
+    fn export(write: Exporter, install_dir: Option<&Path>, mods: &[&WFile]) -> Result<String> {
+        let mut out = Vec::new();
+        write(&mut out, install_dir, mods)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn test_rimworld() -> Result<()> {
+        let dir = TempPath::new("rimworld");
+        let about = item_dir(&dir, 294100, 2009463077).join("About");
+        fs::create_dir_all(&about)?;
+        fs::write(
+            about.join("About.xml"),
+            "<ModMetaData>\n  <name>Harmony</name>\n  <packageId>brrainz.Harmony</packageId>\n</ModMetaData>\n",
+        )?;
+
+        let (mut harmony, mut hugslib) = (file(294100, 2009463077), file(294100, 818773962));
+        harmony.metadata.tags = vec!["Mod".to_string(), "1.3".to_string(), "1.4".to_string()];
+        hugslib.metadata.tags = vec!["1.10".to_string()];
+        let config = export(write_rimworld, Some(&dir), &[&harmony, &hugslib])?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(
+            config,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ModsConfigData>
+  <version>1.10</version>
+  <!-- not installed, listed by file ID instead of package ID: 818773962 -->
+  <activeMods>
+    <li>ludeon.rimworld</li>
+    <li>brrainz.harmony</li>
+    <li>818773962</li>
+  </activeMods>
+  <knownExpansions />
+</ModsConfigData>
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zomboid() -> Result<()> {
+        let dir = TempPath::new("zomboid");
+        let mods = item_dir(&dir, 108600, 2169435993).join("mods");
+        for (name, id) in [
+            ("ModOptions", "modoptions"),
+            ("ModOptionsB41", "modoptions41"),
+        ] {
+            fs::create_dir_all(mods.join(name))?;
+            fs::write(
+                mods.join(name).join("mod.info"),
+                format!("name={}\nid={}\n", name, id),
+            )?;
+        }
+
+        let (options, other) = (file(108600, 2169435993), file(108600, 2392709985));
+        let ini = export(write_zomboid, Some(&dir), &[&options, &other])?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(
+            ini,
+            "# not installed, add the mod IDs of these workshop items to Mods= by hand: 2392709985\n\
+             Mods=modoptions;modoptions41\n\
+             WorkshopItems=2169435993;2392709985\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_arma3() -> Result<()> {
+        let (cba, ace) = (file(107410, 450814997), file(107410, 463939057));
+        assert_eq!(
+            export(write_arma3, None, &[&cba, &ace])?,
+            "-mod=steamapps/workshop/content/107410/450814997;steamapps/workshop/content/107410/463939057\n"
+        );
+        assert!(
+            export(write_arma3, Some(Path::new("/srv/arma 3")), &[&cba])?.starts_with("\"-mod=")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,637 @@
+//! Writing the mod order of a collection to a file, as selected by `--save=<format>`.
+
+mod games;
+
+use crate::{
+    net::get_published_file_details::Metadata,
+    prelude::*,
+    workshop::{WFile, WFiles},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Display,
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    /// One file ID per line.
+    Simple,
+    /// RFC 4180 CSV with a header row.
+    Csv,
+    /// Tab-separated values with a header row.
+    Tsv,
+    /// The collection and its mods, see [`SavedOrder`].
+    Json,
+    /// The same as `Json`, in TOML.
+    Toml,
+    /// RimWorld's `ModsConfig.xml`, listing the base game and the mods.
+    RimWorld,
+    /// The `Mods=` and `WorkshopItems=` lines of a Project Zomboid server ini.
+    Zomboid,
+    /// An Arma 3 `-mod=` launch parameter.
+    Arma3,
+}
+
+impl SaveFormat {
+    pub const NAMES: &'static [&'static str] = &[
+        "simple", "csv", "tsv", "json", "toml", "rimworld", "zomboid", "arma3",
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SaveFormat::Simple | SaveFormat::Csv => "csv",
+            SaveFormat::Tsv => "tsv",
+            SaveFormat::Json => "json",
+            SaveFormat::Toml => "toml",
+            SaveFormat::RimWorld => "xml",
+            SaveFormat::Zomboid => "ini",
+            SaveFormat::Arma3 => "txt",
+        }
+    }
+}
+
+impl FromStr for SaveFormat {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(Self::Simple),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "rimworld" => Ok(Self::RimWorld),
+            "zomboid" => Ok(Self::Zomboid),
+            "arma3" => Ok(Self::Arma3),
+            _ => Err(color_eyre::eyre::eyre!(
+                "unknown format '{}', expected one of {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SaveFormat {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Display for SaveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SaveFormat::Simple => "simple",
+            SaveFormat::Csv => "csv",
+            SaveFormat::Tsv => "tsv",
+            SaveFormat::Json => "json",
+            SaveFormat::Toml => "toml",
+            SaveFormat::RimWorld => "rimworld",
+            SaveFormat::Zomboid => "zomboid",
+            SaveFormat::Arma3 => "arma3",
+        })
+    }
+}
+
+const HEADER: [&str; 15] = [
+    "position",
+    "file_id",
+    "app_id",
+    "title",
+    "time_created",
+    "time_updated",
+    "file_size",
+    "tags",
+    "creator",
+    "subscriptions",
+    "favorited",
+    "visibility",
+    "banned",
+    "preview_url",
+    "file_url",
+];
+
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Quote a CSV field if it contains a comma, a quote or a line break, doubling any quotes.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// TSV fields cannot be quoted, so tabs and line breaks are replaced by spaces.
+fn tsv_field(field: &str) -> Cow<'_, str> {
+    if field.contains(['\t', '\r', '\n']) {
+        field.replace(['\t', '\r', '\n'], " ").into()
+    } else {
+        field.into()
+    }
+}
+
+/// Title of the saved order of mods requested on their own.
+pub const STANDALONE_TITLE: &str = "Standalone mods";
+
+/// What a saved mod order is the order of.
+#[derive(Debug, Clone, Copy)]
+pub enum Order<'a> {
+    Collection(&'a WFile),
+    /// Mods of the app `app_id` requested on their own, saved in command-line order.
+    Standalone {
+        app_id: FileId,
+    },
+}
+
+impl<'a> Order<'a> {
+    pub fn file_id(&self) -> Option<FileId> {
+        match self {
+            Order::Collection(collection) => Some(collection.file_id),
+            Order::Standalone { .. } => None,
+        }
+    }
+
+    pub fn app_id(&self) -> FileId {
+        match self {
+            Order::Collection(collection) => collection.app_id,
+            Order::Standalone { app_id } => *app_id,
+        }
+    }
+
+    pub fn title(&self) -> &'a str {
+        match self {
+            Order::Collection(collection) => &collection.title,
+            Order::Standalone { .. } => STANDALONE_TITLE,
+        }
+    }
+
+    /// `{id}` of the file name template: the file ID of a collection, or `standalone-<app_id>`.
+    fn id(&self) -> String {
+        match self {
+            Order::Collection(collection) => collection.file_id.to_string(),
+            Order::Standalone { app_id } => format!("standalone-{}", app_id),
+        }
+    }
+
+    /// When the collection was created and last updated, or the earliest creation and latest update among standalone `mods`.
+    fn times(&self, mods: &[&WFile]) -> (DateTime<Utc>, DateTime<Utc>) {
+        match self {
+            Order::Collection(collection) => (collection.time_created, collection.time_updated),
+            Order::Standalone { .. } => {
+                let now = Utc::now();
+                (
+                    mods.iter()
+                        .map(|file| file.time_created)
+                        .min()
+                        .unwrap_or(now),
+                    mods.iter()
+                        .map(|file| file.time_updated)
+                        .max()
+                        .unwrap_or(now),
+                )
+            }
+        }
+    }
+}
+
+/// Default file name of a saved collection order, without the extension of the format.
+pub const DEFAULT_FILE_NAME: &str = "{id} {title}";
+
+/// Replace characters that are not allowed in file names on Windows or Unix, and avoid names Windows reserves for devices.
+pub fn sanitize_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    // Windows drops trailing dots and spaces
+    let name = name.trim_end_matches(['.', ' ']).trim_start();
+
+    let stem = name.split('.').next().unwrap_or_default();
+    let reserved = ["CON", "PRN", "AUX", "NUL"].contains(&stem.to_ascii_uppercase().as_str())
+        || (stem.len() == 4
+            && stem.get(..3).is_some_and(|prefix| {
+                ["COM", "LPT"].contains(&prefix.to_ascii_uppercase().as_str())
+            })
+            && stem.as_bytes()[3].is_ascii_digit());
+    match name {
+        "" => "_".to_string(),
+        name if reserved => format!("_{}", name),
+        name => name.to_string(),
+    }
+}
+
+/// File name of the saved order of `mods`, from a template with the placeholders `{id}`, `{title}`,
+/// `{date}` (today) and `{updated}` (when the collection was last updated). The extension of `format` is appended.
+pub fn file_name(
+    template: &str,
+    order: Order,
+    mods: &[&WFile],
+    format: SaveFormat,
+) -> Result<String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            color_eyre::eyre::eyre!("unclosed placeholder in file name template '{}'", template)
+        })? + start;
+        match &rest[start + 1..end] {
+            "id" => name.push_str(&order.id()),
+            "title" => name.push_str(order.title()),
+            "date" => name.push_str(&Utc::now().format("%Y-%m-%d").to_string()),
+            "updated" => name.push_str(&order.times(mods).1.format("%Y-%m-%d").to_string()),
+            other => {
+                return Err(color_eyre::eyre::eyre!(
+                    "unknown placeholder {{{}}} in file name template '{}', expected {{id}}, {{title}}, {{date}} or {{updated}}",
+                    other,
+                    template
+                ))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    name.push_str(rest);
+
+    Ok(format!(
+        "{}.{}",
+        sanitize_file_name(&name),
+        format.extension()
+    ))
+}
+
+/// A collection and its mods in load order, as saved by `--save=json` and `--save=toml`.
+/// Mods requested on their own are saved without a file ID.
+#[derive(Debug, Serialize)]
+pub struct SavedOrder<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<FileId>,
+    pub app_id: FileId,
+    pub title: &'a str,
+    pub time_created: DateTime<Utc>,
+    pub time_updated: DateTime<Utc>,
+    #[serde(flatten)]
+    pub metadata: Option<&'a Metadata>,
+    pub children: Vec<SavedChild<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SavedChild<'a> {
+    /// Position in the load order, starting at 1.
+    pub position: usize,
+    pub file_id: FileId,
+    pub app_id: FileId,
+    pub title: &'a str,
+    pub time_created: DateTime<Utc>,
+    pub time_updated: DateTime<Utc>,
+    #[serde(flatten)]
+    pub metadata: &'a Metadata,
+    /// The collection listing the mod, which is a linked collection for mods not listed directly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<FileId>,
+    /// `sortorder` of the mod in that collection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sortorder: Option<u32>,
+}
+
+impl<'a> SavedOrder<'a> {
+    pub fn new(wfiles: &'a WFiles, order: Order<'a>, mods: &[&'a WFile]) -> Self {
+        let parents = match order {
+            Order::Collection(collection) => parents(wfiles, collection),
+            Order::Standalone { .. } => HashMap::new(),
+        };
+        let children = mods
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let parent = match order {
+                    Order::Collection(collection) => {
+                        Some(parents.get(&file.file_id).copied().unwrap_or(collection))
+                    }
+                    Order::Standalone { .. } => None,
+                };
+                SavedChild {
+                    position: i + 1,
+                    file_id: file.file_id,
+                    app_id: file.app_id,
+                    title: &file.title,
+                    time_created: file.time_created,
+                    time_updated: file.time_updated,
+                    metadata: &file.metadata,
+                    collection: parent.map(|parent| parent.file_id),
+                    sortorder: parent
+                        .and_then(|parent| parent.sortorders.get(&file.file_id).copied()),
+                }
+            })
+            .collect();
+
+        let (time_created, time_updated) = order.times(mods);
+        Self {
+            file_id: order.file_id(),
+            app_id: order.app_id(),
+            title: order.title(),
+            time_created,
+            time_updated,
+            metadata: match order {
+                Order::Collection(collection) => Some(&collection.metadata),
+                Order::Standalone { .. } => None,
+            },
+            children,
+        }
+    }
+}
+
+/// The collection each mod is first listed in, walking linked collections in the same order as [`WFiles::expand_collection`].
+fn parents<'a>(wfiles: &'a WFiles, collection: &'a WFile) -> HashMap<FileId, &'a WFile> {
+    fn walk<'a>(
+        wfiles: &'a WFiles,
+        collection: &'a WFile,
+        parents: &mut HashMap<FileId, &'a WFile>,
+    ) {
+        for file in collection
+            .children
+            .iter()
+            .flatten()
+            .filter_map(|id| wfiles.get(id))
+        {
+            if parents.contains_key(&file.file_id) {
+                continue;
+            }
+            parents.insert(file.file_id, collection);
+            if file.is_collection() {
+                walk(wfiles, file, parents);
+            }
+        }
+    }
+
+    let mut parents = HashMap::new();
+    parents.insert(collection.file_id, collection);
+    walk(wfiles, collection, &mut parents);
+    parents
+}
+
+/// Write `mods` in load order. `install_dir` is the download location, if known,
+/// which the game-specific formats look up mod IDs and paths in.
+pub fn write_order(
+    w: &mut impl Write,
+    format: SaveFormat,
+    wfiles: &WFiles,
+    order: Order,
+    mods: &[&WFile],
+    install_dir: Option<&Path>,
+) -> Result<()> {
+    match format {
+        SaveFormat::Simple => {
+            for file in mods {
+                writeln!(w, "{}", file.file_id)?;
+            }
+        }
+        SaveFormat::Csv => write_table(w, mods, ",", "\r\n", csv_field)?,
+        SaveFormat::Tsv => write_table(w, mods, "\t", "\n", tsv_field)?,
+        SaveFormat::Json => {
+            serde_json::to_writer_pretty(&mut *w, &SavedOrder::new(wfiles, order, mods))?;
+            writeln!(w)?;
+        }
+        SaveFormat::Toml => {
+            let order = SavedOrder::new(wfiles, order, mods);
+            w.write_all(toml::to_string_pretty(&order)?.as_bytes())?;
+        }
+        SaveFormat::RimWorld => games::write_rimworld(w, install_dir, mods)?,
+        SaveFormat::Zomboid => games::write_zomboid(w, install_dir, mods)?,
+        SaveFormat::Arma3 => games::write_arma3(w, install_dir, mods)?,
+    }
+    w.flush()?;
+    Ok(())
+}
+
+fn write_table(
+    w: &mut impl Write,
+    mods: &[&WFile],
+    separator: &str,
+    terminator: &str,
+    escape: fn(&str) -> Cow<'_, str>,
+) -> io::Result<()> {
+    write!(w, "{}{}", HEADER.join(separator), terminator)?;
+    // missing values are left empty
+    fn opt(value: Option<impl ToString>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    for (i, file) in mods.iter().enumerate() {
+        let metadata = &file.metadata;
+        let row = [
+            (i + 1).to_string(),
+            file.file_id.to_string(),
+            file.app_id.to_string(),
+            file.title.clone(),
+            timestamp(&file.time_created),
+            timestamp(&file.time_updated),
+            opt(metadata.file_size),
+            metadata.tags.join(";"),
+            opt(metadata.creator.as_deref()),
+            opt(metadata.subscriptions),
+            opt(metadata.favorited),
+            opt(metadata.visibility_name()),
+            opt(metadata.banned),
+            opt(metadata.preview_url.as_deref()),
+            opt(metadata.file_url.as_deref()),
+        ];
+        let row = row.iter().map(|field| escape(field)).collect::<Vec<_>>();
+        write!(w, "{}{}", row.join(separator), terminator)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::mock;
+    use chrono::TimeZone;
+    use std::collections::HashSet;
+
+    fn file(file_id: FileId, title: &str) -> WFile {
+        WFile {
+            file_id,
+            app_id: 294100,
+            children: None,
+            sortorders: HashMap::new(),
+            title: title.into(),
+            description: String::new(),
+            time_created: Utc.timestamp_opt(1479427200, 0).unwrap(),
+            time_updated: Utc.timestamp_opt(1658102400, 0).unwrap(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_write_order() -> Result<()> {
+        let collection = WFile {
+            children: Some(vec![818773962, 2009463077]),
+            sortorders: HashMap::from([(818773962, 1), (2009463077, 2)]),
+            ..file(368330611, "Essential Mods")
+        };
+        let hugslib = WFile {
+            metadata: Metadata {
+                file_size: Some(1048576),
+                tags: vec!["Mod".into(), "1.4".into()],
+                creator: Some("76561197990000001".into()),
+                banned: Some(false),
+                ..Default::default()
+            },
+            ..file(818773962, "HugsLib")
+        };
+        let harmony = file(2009463077, "Harmony, \"the\"\tlibrary");
+        let mods = [&hugslib, &harmony];
+        let wfiles = WFiles {
+            all_files: HashMap::new(),
+            invalid: HashMap::new(),
+        };
+
+        let write = |format| -> Result<String> {
+            let mut out = Vec::new();
+            write_order(
+                &mut out,
+                format,
+                &wfiles,
+                Order::Collection(&collection),
+                &mods,
+                None,
+            )?;
+            Ok(String::from_utf8(out)?)
+        };
+
+        assert_eq!(write(SaveFormat::Simple)?, "818773962\n2009463077\n");
+        assert_eq!(
+            write(SaveFormat::Csv)?,
+            "position,file_id,app_id,title,time_created,time_updated,file_size,tags,creator,subscriptions,favorited,visibility,banned,preview_url,file_url\r\n\
+             1,818773962,294100,HugsLib,2016-11-18T00:00:00Z,2022-07-18T00:00:00Z,1048576,Mod;1.4,76561197990000001,,,,false,,\r\n\
+             2,2009463077,294100,\"Harmony, \"\"the\"\"\tlibrary\",2016-11-18T00:00:00Z,2022-07-18T00:00:00Z,,,,,,,,,\r\n"
+        );
+        assert_eq!(
+            write(SaveFormat::Tsv)?,
+            "position\tfile_id\tapp_id\ttitle\ttime_created\ttime_updated\tfile_size\ttags\tcreator\tsubscriptions\tfavorited\tvisibility\tbanned\tpreview_url\tfile_url\n\
+             1\t818773962\t294100\tHugsLib\t2016-11-18T00:00:00Z\t2022-07-18T00:00:00Z\t1048576\tMod;1.4\t76561197990000001\t\t\t\tfalse\t\t\n\
+             2\t2009463077\t294100\tHarmony, \"the\" library\t2016-11-18T00:00:00Z\t2022-07-18T00:00:00Z\t\t\t\t\t\t\t\t\t\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_name() -> Result<()> {
+        let collection = file(368330611, "Essential: Mods/1.4 <b>");
+        assert_eq!(
+            file_name(
+                DEFAULT_FILE_NAME,
+                Order::Collection(&collection),
+                &[],
+                SaveFormat::Csv
+            )?,
+            "368330611 Essential_ Mods_1.4 _b_.csv"
+        );
+        assert_eq!(
+            file_name(
+                "{id} {title} {updated}",
+                Order::Standalone { app_id: 294100 },
+                &[&collection],
+                SaveFormat::Csv
+            )?,
+            "standalone-294100 Standalone mods 2022-07-18.csv"
+        );
+        assert_eq!(
+            file_name(
+                "{title} {updated}",
+                Order::Collection(&file(1, "Con Air...")),
+                &[],
+                SaveFormat::Json
+            )?,
+            "Con Air... 2022-07-18.json"
+        );
+        assert_eq!(
+            file_name(
+                "{title}",
+                Order::Collection(&file(1, "con")),
+                &[],
+                SaveFormat::Tsv
+            )?,
+            "_con.tsv"
+        );
+        assert_eq!(sanitize_file_name(" com1.txt"), "_com1.txt");
+        assert_eq!(sanitize_file_name("..."), "_");
+        for name in ["abé", "xyé", "1€", "Cöm1"] {
+            assert_eq!(sanitize_file_name(name), name);
+        }
+        let order = Order::Collection(&collection);
+        assert!(file_name("{name}", order, &[], SaveFormat::Csv).is_err());
+        assert!(file_name("{id", order, &[], SaveFormat::Csv).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_structured() -> Result<()> {
+        mock::serve();
+        let wfiles = WFiles::resolve(&[368330611], 8)?;
+        let collection = wfiles.get(&368330611).unwrap();
+        let mut mods = Vec::new();
+        wfiles.expand_collection(collection, false, &mut HashSet::new(), &mut mods)?;
+
+        let order = SavedOrder::new(&wfiles, Order::Collection(collection), &mods);
+        assert_eq!(order.title, "Essential Mods");
+        assert_eq!(
+            order
+                .children
+                .iter()
+                .map(|c| (c.file_id, c.collection, c.sortorder))
+                .collect::<Vec<_>>(),
+            [
+                (818773962, Some(368330611), Some(1)),
+                (2009463077, Some(368330611), Some(2)),
+                (1541438856, Some(1541984105), Some(1)),
+                (761421485, Some(368330611), Some(5)),
+            ]
+        );
+
+        let mut json = Vec::new();
+        write_order(
+            &mut json,
+            SaveFormat::Json,
+            &wfiles,
+            Order::Collection(collection),
+            &mods,
+            None,
+        )?;
+        let json: serde_json::Value = serde_json::from_slice(&json)?;
+        assert_eq!(json["children"][2]["title"], "Numbers");
+        assert_eq!(json["children"][2]["tags"][0], "Mod");
+        assert!(json["children"][2]["file_size"].is_u64());
+        assert_eq!(json["time_updated"], "2022-09-01T00:00:00Z");
+
+        let mut toml = Vec::new();
+        write_order(
+            &mut toml,
+            SaveFormat::Toml,
+            &wfiles,
+            Order::Collection(collection),
+            &mods,
+            None,
+        )?;
+        let toml: toml::Value = toml::from_slice(&toml)?;
+        assert_eq!(toml["children"][3]["file_id"].as_integer(), Some(761421485));
+        assert_eq!(toml["children"][3]["position"].as_integer(), Some(4));
+        assert!(toml["children"][3]["subscriptions"].is_integer());
+        Ok(())
+    }
+}
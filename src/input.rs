@@ -0,0 +1,236 @@
+//! Reading file IDs from lists: text files with one file ID or workshop URL per line,
+//! standard input, and the mod orders written by `--save`.
+
+use crate::{prelude::*, workshop::parse_file_id};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+/// A file ID given on the command line, or `-` to read file IDs from standard input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileArg {
+    Id(FileId),
+    Stdin,
+}
+
+pub fn parse_file_arg(s: &str) -> Result<FileArg> {
+    match s {
+        "-" => Ok(FileArg::Stdin),
+        s => parse_file_id(s).map(FileArg::Id),
+    }
+}
+
+/// Strip a `#` comment, which starts a line or follows whitespace so that URL fragments survive.
+fn strip_comment(line: &str) -> &str {
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        if c == '#' && prev.is_whitespace() {
+            return &line[..i];
+        }
+        prev = c;
+    }
+    line
+}
+
+/// Split CSV text into records, numbered by the line they start on. Quoted fields may hold commas,
+/// doubled quotes and line breaks.
+fn csv_records(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let (mut line, mut start) = (1, 1);
+    let mut quoted = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            '\r' if !quoted => {}
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+    records
+}
+
+/// Read one file ID or workshop URL per line, ignoring blank lines and `#` comments.
+///
+/// Tables written by `--save=csv` or `--save=tsv` are recognised by the separator in their first line, and read from
+/// their first field, or from the `file_id` column if the first row is a header naming it. Any other header row is skipped.
+pub fn read_file_ids(mut reader: impl BufRead) -> Result<Vec<FileId>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let first = text
+        .lines()
+        .map(|line| strip_comment(line).trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    let records = match first.find(['\t', ',']).map(|i| &first[i..i + 1]) {
+        None => text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, vec![strip_comment(line).to_string()]))
+            .collect(),
+        Some("\t") => text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.split('\t').map(str::to_string).collect()))
+            .collect(),
+        Some(_) => csv_records(&text),
+    };
+
+    let mut ids = Vec::new();
+    let mut column = 0;
+    let mut first = true;
+    for (n, fields) in records {
+        let fields = fields.iter().map(|f| f.trim()).collect::<Vec<_>>();
+        if fields.iter().all(|f| f.is_empty()) || fields[0].starts_with('#') {
+            continue;
+        }
+
+        let is_first = std::mem::take(&mut first);
+        let field = fields.get(column).copied().unwrap_or_default();
+        match parse_file_id(field) {
+            Ok(id) => ids.push(id),
+            Err(_) if is_first => {
+                // a header row
+                if let Some(i) = fields.iter().position(|f| *f == "file_id") {
+                    column = i;
+                }
+            }
+            Err(e) => return Err(e.wrap_err(format!("line {}", n))),
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Read file IDs from a file, or from standard input if `path` is `-`.
+pub fn read_file_ids_from(path: &Path) -> Result<Vec<FileId>> {
+    if path == Path::new("-") {
+        read_file_ids(io::stdin().lock())
+    } else {
+        read_file_ids(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_file_ids() -> Result<()> {
+        let list = "\
+# server mods
+368330611
+https://steamcommunity.com/sharedfiles/filedetails/?id=2824342092#comments   # Performance Fish
+
+steam://url/CommunityFilePage/1626860092
+";
+        assert_eq!(
+            read_file_ids(list.as_bytes())?,
+            [368330611, 2824342092, 1626860092]
+        );
+        assert!(read_file_ids("368330611\nabc\n".as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_saved() -> Result<()> {
+        let simple = "818773962\n2009463077\n";
+        assert_eq!(read_file_ids(simple.as_bytes())?, [818773962, 2009463077]);
+
+        let tsv = "818773962\tHugsLib\t2016-11-18 00:00:00 UTC\t2022-07-18 00:00:00 UTC\n\
+                   2009463077\tHarmony\t2020-02-28 00:00:00 UTC\t2022-08-01 00:00:00 UTC\n";
+        assert_eq!(read_file_ids(tsv.as_bytes())?, [818773962, 2009463077]);
+
+        let csv =
+            "position,file_id,title\n1,818773962,HugsLib\n2,2009463077,\"Harmony, the library\"\n";
+        assert_eq!(read_file_ids(csv.as_bytes())?, [818773962, 2009463077]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_saved_round_trip() -> Result<()> {
+        use crate::{
+            export::{write_order, Order, SaveFormat},
+            workshop::{WFile, WFiles},
+        };
+        use chrono::Utc;
+
+        let mods = ["Tab\there, \"quoted\"", "Line\nbreak # not a comment"]
+            .iter()
+            .zip([818773962, 2009463077])
+            .map(|(title, file_id)| WFile {
+                file_id,
+                app_id: 294100,
+                children: None,
+                sortorders: Default::default(),
+                title: title.to_string(),
+                description: String::new(),
+                time_created: Utc::now(),
+                time_updated: Utc::now(),
+                metadata: Default::default(),
+            })
+            .collect::<Vec<_>>();
+        let mods = mods.iter().collect::<Vec<_>>();
+        let wfiles = WFiles {
+            all_files: Default::default(),
+            invalid: Default::default(),
+        };
+
+        for format in [SaveFormat::Csv, SaveFormat::Tsv] {
+            let mut saved = Vec::new();
+            write_order(
+                &mut saved,
+                format,
+                &wfiles,
+                Order::Standalone { app_id: 294100 },
+                &mods,
+                None,
+            )?;
+            assert_eq!(
+                read_file_ids(saved.as_slice())?,
+                [818773962, 2009463077],
+                "{:?}",
+                format
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file_arg() {
+        assert_eq!(parse_file_arg("-").unwrap(), FileArg::Stdin);
+        assert_eq!(parse_file_arg("368330611").unwrap(), FileArg::Id(368330611));
+        assert!(parse_file_arg("--").is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! Resolve Steam Workshop items and collections through the Steam Web API, and assemble SteamCMD scripts to download them.
+//!
+//! - [`net`] wraps the `ISteamRemoteStorage` endpoints and `IPublishedFileService/GetDetails`, with [`net::file_details`]
+//!   retrieving item details from the backend selected with [`net::set_backend`].
+//! - [`workshop`] resolves requested file ids, including linked collections, into [`workshop::WFile`]s.
+//! - [`input`] reads file ids from lists and standard input.
+//! - [`export`] writes the mod orders of collections.
+//! - [`steamcmd`] builds the SteamCMD invocation that downloads them.
+//! - [`local`] and [`manifest`] inspect what is already installed.
+//! - [`workspace`] keeps named sets of mods and collections.
+//! - [`lock`] pins the contents of collections for reproducible installs.
+//! - [`config`] reads defaults and profiles for the command-line options.
+//! - [`cli`] is the command-line front end used by the `swd` binary.
+
+pub mod cli;
+pub mod config;
+pub mod export;
+pub mod input;
+pub mod local;
+pub mod lock;
+pub mod manifest;
+pub mod net;
+pub mod prelude;
+pub mod steamcmd;
+mod util;
+pub mod workshop;
+pub mod workspace;
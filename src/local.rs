@@ -0,0 +1,292 @@
+//! What is already installed in a SteamCMD install directory.
+//!
+//! SteamCMD keeps a record of the workshop items it installed for each app in
+//! `steamapps/workshop/appworkshop_<app_id>.acf`, including the `timeupdated` of the installed version.
+//! swd's own [`Manifest`] takes precedence over it, and items missing from both fall back to the
+//! modification time of their folder under `steamapps/workshop/content/<app_id>/<file_id>`.
+
+use crate::{manifest::Manifest, prelude::*, workshop::WFile};
+use chrono::{DateTime, TimeZone, Utc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Folder SteamCMD installs the workshop items of `app_id` into.
+pub fn content_dir(install_dir: &Path, app_id: FileId) -> PathBuf {
+    install_dir
+        .join("steamapps")
+        .join("workshop")
+        .join("content")
+        .join(app_id.to_string())
+}
+
+/// Folder SteamCMD installs a single workshop item into.
+pub fn item_dir(install_dir: &Path, app_id: FileId, file_id: FileId) -> PathBuf {
+    content_dir(install_dir, app_id).join(file_id.to_string())
+}
+
+/// SteamCMD's record of the workshop items installed for `app_id`.
+pub fn acf_path(install_dir: &Path, app_id: FileId) -> PathBuf {
+    install_dir
+        .join("steamapps")
+        .join("workshop")
+        .join(format!("appworkshop_{}.acf", app_id))
+}
+
+/// Installed versions of workshop items under an install directory, loaded lazily per app.
+#[derive(Debug)]
+pub struct LocalState {
+    install_dir: PathBuf,
+    manifest: Manifest,
+    apps: HashMap<FileId, HashMap<FileId, DateTime<Utc>>>,
+}
+
+impl LocalState {
+    pub fn new(install_dir: impl Into<PathBuf>) -> Self {
+        let install_dir = install_dir.into();
+        Self {
+            manifest: Manifest::load(&install_dir).unwrap_or_default(),
+            install_dir,
+            apps: HashMap::new(),
+        }
+    }
+
+    /// When the installed version of an item was last updated, `None` if it is not installed.
+    /// The manifest is only trusted while the folder of the item still exists.
+    pub fn installed_time(&mut self, app_id: FileId, file_id: FileId) -> Option<DateTime<Utc>> {
+        let install_dir = &self.install_dir;
+        if let Some(item) = self.manifest.get(file_id) {
+            if item_dir(install_dir, app_id, file_id).exists() {
+                return Some(item.time_updated);
+            }
+        }
+
+        let recorded = self
+            .apps
+            .entry(app_id)
+            .or_insert_with(|| read_acf(&acf_path(install_dir, app_id)).unwrap_or_default())
+            .get(&file_id)
+            .copied();
+
+        recorded.or_else(|| {
+            let modified = fs::metadata(item_dir(install_dir, app_id, file_id))
+                .ok()?
+                .modified()
+                .ok()?;
+            Some(modified.into())
+        })
+    }
+
+    /// Whether the installed version of `file` is at least as recent as the one on the workshop.
+    pub fn is_up_to_date(&mut self, file: &WFile) -> bool {
+        self.installed_time(file.app_id, file.file_id)
+            .is_some_and(|installed| installed >= file.time_updated)
+    }
+}
+
+/// Read the `timeupdated` of every item in the `WorkshopItemsInstalled` section of an `appworkshop_<app_id>.acf`.
+pub fn read_acf(path: &Path) -> Result<HashMap<FileId, DateTime<Utc>>> {
+    let text = fs::read_to_string(path)?;
+    let root = vdf::parse(&text)
+        .ok_or_else(|| color_eyre::eyre::eyre!("malformed acf file {}", path.display()))?;
+
+    let installed = root
+        .get("AppWorkshop")
+        .and_then(|v| v.get("WorkshopItemsInstalled"))
+        .and_then(vdf::Value::as_map)
+        .unwrap_or_default();
+
+    let items = installed
+        .iter()
+        .filter_map(|(file_id, item)| {
+            let file_id = file_id.parse().ok()?;
+            let time_updated = item.get("timeupdated")?.as_str()?.parse().ok()?;
+            let time_updated = Utc.timestamp_opt(time_updated, 0).single()?;
+            Some((file_id, time_updated))
+        })
+        .collect();
+    Ok(items)
+}
+
+/// A minimal reader for Valve's KeyValues text format, as used by `.acf` files.
+mod vdf {
+    #[derive(Debug)]
+    pub enum Value {
+        Str(String),
+        Map(Vec<(String, Value)>),
+    }
+
+    impl Value {
+        pub fn get(&self, key: &str) -> Option<&Value> {
+            self.as_map()?
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v)
+        }
+
+        pub fn as_map(&self) -> Option<&[(String, Value)]> {
+            match self {
+                Value::Map(map) => Some(map),
+                Value::Str(_) => None,
+            }
+        }
+
+        pub fn as_str(&self) -> Option<&str> {
+            match self {
+                Value::Str(s) => Some(s),
+                Value::Map(_) => None,
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Str(String),
+        Open,
+        Close,
+    }
+
+    fn tokenize(text: &str) -> Option<Vec<Token>> {
+        let mut tokens = Vec::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => tokens.push(Token::Open),
+                '}' => tokens.push(Token::Close),
+                '"' => {
+                    let mut s = String::new();
+                    loop {
+                        match chars.next()? {
+                            '"' => break,
+                            '\\' => s.push(chars.next()?),
+                            c => s.push(c),
+                        }
+                    }
+                    tokens.push(Token::Str(s));
+                }
+                '/' if chars.peek() == Some(&'/') => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                c if c.is_whitespace() => {}
+                _ => return None,
+            }
+        }
+        Some(tokens)
+    }
+
+    fn parse_map(tokens: &mut std::vec::IntoIter<Token>, nested: bool) -> Option<Value> {
+        let mut map = Vec::new();
+        loop {
+            let key = match tokens.next() {
+                Some(Token::Str(key)) => key,
+                Some(Token::Close) if nested => break,
+                None if !nested => break,
+                _ => return None,
+            };
+            let value = match tokens.next()? {
+                Token::Str(s) => Value::Str(s),
+                Token::Open => parse_map(tokens, true)?,
+                Token::Close => return None,
+            };
+            map.push((key, value));
+        }
+        Some(Value::Map(map))
+    }
+
+    pub fn parse(text: &str) -> Option<Value> {
+        parse_map(&mut tokenize(text)?.into_iter(), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ManifestItem;
+    use crate::util::TempPath;
+
+    const ACF: &str = r#""AppWorkshop"
+{
+	"appid"		"294100"
+	"SizeOnDisk"		"3791546"
+	"NeedsUpdate"		"0"
+	"NeedsDownload"		"0"
+	"TimeLastUpdated"		"1662000000"
+	"TimeLastAppRan"		"0"
+	"WorkshopItemsInstalled"
+	{
+		"818773962"
+		{
+			"size"		"2587990"
+			"timeupdated"		"1658102400"
+			"manifest"		"5531294391563957411"
+		}
+		"2009463077"
+		{
+			"size"		"1203556"
+			"timeupdated"		"1600000000"
+			"manifest"		"1812034839201759911"
+		}
+	}
+	"WorkshopItemDetails"
+	{
+	}
+}
+"#;
+
+    #[test]
+    fn test_read_acf() -> Result<()> {
+        let dir = TempPath::new("read_acf");
+        fs::create_dir_all(dir.join("steamapps").join("workshop"))?;
+        fs::write(acf_path(&dir, 294100), ACF)?;
+
+        let items = read_acf(&acf_path(&dir, 294100))?;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[&818773962].timestamp(), 1658102400);
+
+        let mut state = LocalState::new(&*dir);
+        assert_eq!(
+            state
+                .installed_time(294100, 2009463077)
+                .unwrap()
+                .timestamp(),
+            1600000000
+        );
+        assert_eq!(state.installed_time(294100, 761421485), None);
+
+        fs::create_dir_all(item_dir(&dir, 294100, 761421485))?;
+        assert!(state.installed_time(294100, 761421485).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_deleted_item() -> Result<()> {
+        let dir = TempPath::new("manifest_deleted_item");
+        let path = item_dir(&dir, 294100, 1);
+        let mut manifest = Manifest::default();
+        manifest.insert(ManifestItem {
+            file_id: 1,
+            app_id: 294100,
+            title: String::new(),
+            time_updated: Utc.timestamp_opt(1600000000, 0).unwrap(),
+            collection: None,
+            path: path.clone(),
+            installed_at: Utc::now(),
+        });
+        manifest.save(&dir)?;
+
+        fs::create_dir_all(&path)?;
+        let installed = LocalState::new(&*dir).installed_time(294100, 1);
+        assert_eq!(installed.map(|time| time.timestamp()), Some(1600000000));
+
+        // a deleted item is not installed, whatever the manifest says
+        fs::remove_dir_all(&path)?;
+        assert_eq!(LocalState::new(&*dir).installed_time(294100, 1), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+//! A lockfile recording the exact contents of collections and the version of every item at the time they were locked,
+//! so that later installs can tell when a collection changed under them.
+
+use crate::{
+    prelude::*,
+    workshop::{WFile, WFiles},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    path::Path,
+};
+
+pub const LOCKFILE_NAME: &str = "swd.lock";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedCollection {
+    pub file_id: FileId,
+    /// Missing from lockfiles written before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<FileId>,
+    pub title: String,
+    pub time_updated: DateTime<Utc>,
+    /// Mods and linked collections in collection order.
+    pub children: Vec<FileId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedItem {
+    pub file_id: FileId,
+    pub app_id: FileId,
+    pub title: String,
+    pub time_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lock {
+    /// The file ids the lock was created from.
+    pub roots: Vec<FileId>,
+    #[serde(rename = "collection", default)]
+    pub collections: Vec<LockedCollection>,
+    #[serde(rename = "item", default)]
+    pub items: Vec<LockedItem>,
+}
+
+/// A difference between a lockfile and the live state of the workshop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// A collection gained a child.
+    Added { collection: FileId, file_id: FileId },
+    /// A collection lost a child.
+    Removed { collection: FileId, file_id: FileId },
+    /// A collection kept its children but changed their order.
+    Reordered { collection: FileId },
+    /// An item was updated on the workshop.
+    Updated {
+        file_id: FileId,
+        locked: DateTime<Utc>,
+        live: DateTime<Utc>,
+    },
+    /// A locked item or collection is no longer available.
+    Missing { file_id: FileId },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Divergence::Added {
+                collection,
+                file_id,
+            } => write!(f, "{} was added to collection {}", file_id, collection),
+            Divergence::Removed {
+                collection,
+                file_id,
+            } => write!(f, "{} was removed from collection {}", file_id, collection),
+            Divergence::Reordered { collection } => {
+                write!(f, "collection {} was reordered", collection)
+            }
+            Divergence::Updated {
+                file_id,
+                locked,
+                live,
+            } => write!(
+                f,
+                "{} was updated at {}, locked at {}",
+                file_id,
+                live.naive_local(),
+                locked.naive_local()
+            ),
+            Divergence::Missing { file_id } => write!(f, "{} is no longer available", file_id),
+        }
+    }
+}
+
+impl Lock {
+    /// Lock the resolved items. `roots` are the file ids they were resolved from.
+    pub fn new(roots: &[FileId], wfiles: &WFiles) -> Self {
+        let mut collections = Vec::new();
+        let mut items = Vec::new();
+        for file in wfiles.all_files.values() {
+            match file.children.as_ref() {
+                Some(children) => collections.push(LockedCollection {
+                    file_id: file.file_id,
+                    app_id: Some(file.app_id),
+                    title: file.title.clone(),
+                    time_updated: file.time_updated,
+                    children: children.clone(),
+                }),
+                None => items.push(LockedItem {
+                    file_id: file.file_id,
+                    app_id: file.app_id,
+                    title: file.title.clone(),
+                    time_updated: file.time_updated,
+                }),
+            }
+        }
+        collections.sort_by_key(|c| c.file_id);
+        items.sort_by_key(|i| i.file_id);
+
+        Self {
+            roots: roots
+                .iter()
+                .copied()
+                .filter(|id| wfiles.contains(id))
+                .collect(),
+            collections,
+            items,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let lock = toml::from_str(&fs::read_to_string(path)?)?;
+        Ok(lock)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The locked collections and items as resolved items, so that installs download what was locked
+    /// rather than the current contents of the collections. Details the lock does not record are taken from `live` where available.
+    /// Fails if the app of a collection is neither recorded nor known from `live` or its items.
+    pub fn wfiles(&self, live: &WFiles) -> Result<WFiles> {
+        let file = |file_id, app_id, title: &str, time_updated, children| {
+            let live = live.get(&file_id);
+            WFile {
+                file_id,
+                app_id: live.map_or(app_id, |f| f.app_id),
+                children,
+                sortorders: live.map(|f| f.sortorders.clone()).unwrap_or_default(),
+                title: title.to_string(),
+                description: live.map(|f| f.description.clone()).unwrap_or_default(),
+                time_created: live.map_or(time_updated, |f| f.time_created),
+                time_updated,
+                metadata: live.map(|f| f.metadata.clone()).unwrap_or_default(),
+            }
+        };
+
+        let mut all_files = HashMap::new();
+        for item in self.items.iter() {
+            let wfile = file(
+                item.file_id,
+                item.app_id,
+                &item.title,
+                item.time_updated,
+                None,
+            );
+            all_files.insert(item.file_id, wfile);
+        }
+        for collection in self.collections.iter() {
+            let app_id = collection
+                .app_id
+                .or_else(|| live.get(&collection.file_id).map(|f| f.app_id))
+                .or_else(|| self.items_app_id(collection, &mut HashSet::new()))
+                .ok_or_else(|| {
+                    color_eyre::eyre::eyre!(
+                        "the app of collection {} is unknown, lock it again to record it",
+                        collection.file_id
+                    )
+                })?;
+            let wfile = file(
+                collection.file_id,
+                app_id,
+                &collection.title,
+                collection.time_updated,
+                Some(collection.children.clone()),
+            );
+            all_files.insert(collection.file_id, wfile);
+        }
+
+        Ok(WFiles {
+            all_files,
+            invalid: HashMap::new(),
+        })
+    }
+
+    /// The app of the first locked item of `collection`, searching linked collections too.
+    fn items_app_id(
+        &self,
+        collection: &LockedCollection,
+        visited: &mut HashSet<FileId>,
+    ) -> Option<FileId> {
+        if !visited.insert(collection.file_id) {
+            return None;
+        }
+        collection.children.iter().find_map(|id| {
+            match self.items.iter().find(|i| i.file_id == *id) {
+                Some(item) => Some(item.app_id),
+                None => self
+                    .collections
+                    .iter()
+                    .find(|c| c.file_id == *id)
+                    .and_then(|c| self.items_app_id(c, visited)),
+            }
+        })
+    }
+
+    /// Compare the lock against freshly resolved items.
+    pub fn diff(&self, wfiles: &WFiles) -> Vec<Divergence> {
+        let mut divergences = Vec::new();
+
+        for locked in self.collections.iter() {
+            let Some(live) = wfiles
+                .get(&locked.file_id)
+                .and_then(|f| f.children.as_ref())
+            else {
+                divergences.push(Divergence::Missing {
+                    file_id: locked.file_id,
+                });
+                continue;
+            };
+
+            let locked_set = locked.children.iter().collect::<HashSet<_>>();
+            let live_set = live.iter().collect::<HashSet<_>>();
+            divergences.extend(
+                live.iter()
+                    .filter(|id| !locked_set.contains(id))
+                    .map(|&file_id| Divergence::Added {
+                        collection: locked.file_id,
+                        file_id,
+                    }),
+            );
+            divergences.extend(
+                locked
+                    .children
+                    .iter()
+                    .filter(|id| !live_set.contains(id))
+                    .map(|&file_id| Divergence::Removed {
+                        collection: locked.file_id,
+                        file_id,
+                    }),
+            );
+            if locked_set == live_set && &locked.children != live {
+                divergences.push(Divergence::Reordered {
+                    collection: locked.file_id,
+                });
+            }
+        }
+
+        for locked in self.items.iter() {
+            match wfiles.get(&locked.file_id) {
+                Some(live) if live.time_updated != locked.time_updated => {
+                    divergences.push(Divergence::Updated {
+                        file_id: locked.file_id,
+                        locked: locked.time_updated,
+                        live: live.time_updated,
+                    })
+                }
+                Some(_) => {}
+                None => divergences.push(Divergence::Missing {
+                    file_id: locked.file_id,
+                }),
+            }
+        }
+
+        // an item gone from a collection is reported as removed from it, not also as missing
+        let removed = divergences
+            .iter()
+            .filter_map(|d| match d {
+                Divergence::Removed { file_id, .. } => Some(*file_id),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        divergences
+            .retain(|d| !matches!(d, Divergence::Missing { file_id } if removed.contains(file_id)));
+        divergences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::mock;
+    use crate::util::TempPath;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_lock() -> Result<()> {
+        mock::serve();
+        let wfiles = WFiles::resolve(&[368330611, 1111], 8)?;
+        let lock = Lock::new(&[368330611, 1111], &wfiles);
+
+        assert_eq!(lock.roots, [368330611]);
+        assert_eq!(
+            lock.collections
+                .iter()
+                .map(|c| c.file_id)
+                .collect::<Vec<_>>(),
+            [368330611, 1541984105]
+        );
+        assert_eq!(
+            lock.items.iter().map(|i| i.file_id).collect::<Vec<_>>(),
+            [761421485, 818773962, 1541438856, 2009463077]
+        );
+        assert!(lock.diff(&wfiles).is_empty());
+
+        let path = TempPath::new("lock.lock");
+        lock.save(&path)?;
+        let loaded = Lock::load(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(loaded, lock);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wfiles() -> Result<()> {
+        mock::serve();
+        let live = WFiles::resolve(&[368330611], 8)?;
+        let mut lock = Lock::new(&[368330611], &live);
+        lock.collections[0].children.retain(|&id| id != 2009463077);
+        lock.items.retain(|i| i.file_id != 2009463077);
+
+        let locked = lock.wfiles(&live)?;
+        assert_eq!(
+            locked.get(&368330611).unwrap().children.as_deref().unwrap(),
+            [818773962, 1541984105, 761421485]
+        );
+        assert!(!locked.contains(&2009463077));
+        assert_eq!(locked.get(&818773962).unwrap().app_id, 294100);
+        assert_eq!(locked.all_files.len(), live.all_files.len() - 1);
+
+        // lockfiles without the app of a collection take it from its items, even through linked collections
+        let no_live = WFiles {
+            all_files: HashMap::new(),
+            invalid: HashMap::new(),
+        };
+        for collection in lock.collections.iter_mut() {
+            collection.app_id = None;
+        }
+        lock.collections[0].children.retain(|&id| id == 1541984105);
+        let locked = lock.wfiles(&no_live)?;
+        assert_eq!(locked.get(&368330611).unwrap().app_id, 294100);
+
+        lock.items.clear();
+        assert!(lock.wfiles(&no_live).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        mock::serve();
+        let wfiles = WFiles::resolve(&[368330611], 8)?;
+        let mut lock = Lock::new(&[368330611], &wfiles);
+
+        let collection = &mut lock.collections[0];
+        collection.children.retain(|&id| id != 2009463077);
+        collection.children.push(1111);
+        lock.items.push(LockedItem {
+            file_id: 1111,
+            app_id: 294100,
+            title: String::new(),
+            time_updated: Utc::now(),
+        });
+        lock.collections[1].children.reverse();
+        lock.items[0].time_updated = Utc.timestamp_opt(1600000000, 0).unwrap();
+
+        assert_eq!(
+            lock.diff(&wfiles),
+            [
+                Divergence::Added {
+                    collection: 368330611,
+                    file_id: 2009463077
+                },
+                Divergence::Removed {
+                    collection: 368330611,
+                    file_id: 1111
+                },
+                Divergence::Reordered {
+                    collection: 1541984105
+                },
+                Divergence::Updated {
+                    file_id: 761421485,
+                    locked: Utc.timestamp_opt(1600000000, 0).unwrap(),
+                    live: Utc.timestamp_opt(1657497600, 0).unwrap(),
+                },
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! A record of the workshop items swd installed into a download location, kept in `swd_manifest.json` next to `steamapps`.
+
+use crate::{
+    prelude::*,
+    steamcmd::{DownloadOutcome, DownloadReport},
+    workshop::WFiles,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST_NAME: &str = "swd_manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestItem {
+    pub file_id: FileId,
+    pub app_id: FileId,
+    pub title: String,
+    /// `time_updated` of the version that was installed.
+    pub time_updated: DateTime<Utc>,
+    /// The collection the item was requested through, `None` if it was only requested on its own.
+    pub collection: Option<FileId>,
+    pub path: PathBuf,
+    pub installed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Installed items, ordered by file id.
+    pub items: Vec<ManifestItem>,
+}
+
+impl Manifest {
+    pub fn path(install_dir: &Path) -> PathBuf {
+        install_dir.join(MANIFEST_NAME)
+    }
+
+    /// Load the manifest of a download location, or an empty one if there is none yet.
+    pub fn load(install_dir: &Path) -> Result<Self> {
+        let path = Self::path(install_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let manifest = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        Ok(manifest)
+    }
+
+    pub fn save(&self, install_dir: &Path) -> Result<()> {
+        fs::create_dir_all(install_dir)?;
+        fs::write(Self::path(install_dir), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, file_id: FileId) -> Option<&ManifestItem> {
+        self.items.iter().find(|item| item.file_id == file_id)
+    }
+
+    /// Add an item, replacing any earlier record of the same file id.
+    pub fn insert(&mut self, item: ManifestItem) {
+        match self
+            .items
+            .binary_search_by_key(&item.file_id, |item| item.file_id)
+        {
+            Ok(i) => self.items[i] = item,
+            Err(i) => self.items.insert(i, item),
+        }
+    }
+
+    /// Record every item `report` shows as downloaded. `sources` maps items to the collection they were requested through.
+    pub fn record(
+        &mut self,
+        report: &DownloadReport,
+        wfiles: &WFiles,
+        sources: &HashMap<FileId, FileId>,
+    ) {
+        let now = Utc::now();
+        for item in report.items.iter() {
+            let (DownloadOutcome::Success(path), Some(file)) =
+                (&item.outcome, wfiles.get(&item.file_id))
+            else {
+                continue;
+            };
+
+            self.insert(ManifestItem {
+                file_id: file.file_id,
+                app_id: file.app_id,
+                title: file.title.clone(),
+                time_updated: file.time_updated,
+                collection: sources.get(&file.file_id).copied(),
+                path: path.clone(),
+                installed_at: now,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempPath;
+    use chrono::TimeZone;
+
+    fn item(file_id: FileId, title: &str) -> ManifestItem {
+        ManifestItem {
+            file_id,
+            app_id: 294100,
+            title: title.into(),
+            time_updated: Utc.timestamp_opt(1658102400, 0).unwrap(),
+            collection: Some(368330611),
+            path: format!("/srv/mods/steamapps/workshop/content/294100/{}", file_id).into(),
+            installed_at: Utc.timestamp_opt(1662000000, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let dir = TempPath::new("manifest");
+
+        let mut manifest = Manifest::load(&dir)?;
+        assert!(manifest.items.is_empty());
+
+        manifest.insert(item(2009463077, "Harmony"));
+        manifest.insert(item(818773962, "HugsLib"));
+        manifest.insert(item(2009463077, "Harmony 2"));
+        manifest.save(&dir)?;
+
+        let loaded = Manifest::load(&dir)?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(loaded, manifest);
+        assert_eq!(
+            loaded.items.iter().map(|i| i.file_id).collect::<Vec<_>>(),
+            [818773962, 2009463077]
+        );
+        assert_eq!(loaded.get(2009463077).unwrap().title, "Harmony 2");
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! Steam's `EResult` codes, as returned in the `result` field of each item in an API response.
+
+use std::fmt::{self, Display};
+
+/// Why the Steam Web API did not return the details of an item. Code 1 is success and has no variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EResult {
+    Fail,
+    NoConnection,
+    InvalidParam,
+    FileNotFound,
+    Busy,
+    AccessDenied,
+    Timeout,
+    Banned,
+    ServiceUnavailable,
+    LimitExceeded,
+    Revoked,
+    Expired,
+    NoMatch,
+    RateLimitExceeded,
+    /// The result was success, but the item came without its details. Not an `EResult` Steam returns, so it has no code.
+    MissingDetails,
+    Other(u32),
+}
+
+impl EResult {
+    /// `None` for code 1, which is success.
+    pub fn from_code(code: u32) -> Option<Self> {
+        let result = match code {
+            1 => return None,
+            2 => Self::Fail,
+            3 => Self::NoConnection,
+            8 => Self::InvalidParam,
+            9 => Self::FileNotFound,
+            10 => Self::Busy,
+            15 => Self::AccessDenied,
+            16 => Self::Timeout,
+            17 => Self::Banned,
+            20 => Self::ServiceUnavailable,
+            25 => Self::LimitExceeded,
+            26 => Self::Revoked,
+            27 => Self::Expired,
+            42 => Self::NoMatch,
+            84 => Self::RateLimitExceeded,
+            code => Self::Other(code),
+        };
+        Some(result)
+    }
+
+    /// `None` for [`EResult::MissingDetails`], which Steam reports as success.
+    pub fn code(&self) -> Option<u32> {
+        let code = match self {
+            Self::Fail => 2,
+            Self::NoConnection => 3,
+            Self::InvalidParam => 8,
+            Self::FileNotFound => 9,
+            Self::Busy => 10,
+            Self::AccessDenied => 15,
+            Self::Timeout => 16,
+            Self::Banned => 17,
+            Self::ServiceUnavailable => 20,
+            Self::LimitExceeded => 25,
+            Self::Revoked => 26,
+            Self::Expired => 27,
+            Self::NoMatch => 42,
+            Self::RateLimitExceeded => 84,
+            Self::MissingDetails => return None,
+            Self::Other(code) => *code,
+        };
+        Some(code)
+    }
+}
+
+impl Display for EResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::Fail => "request failed",
+            Self::NoConnection => "no connection to Steam",
+            Self::InvalidParam => "invalid file ID",
+            Self::FileNotFound => "not found, the item may have been removed",
+            Self::Busy => "Steam is busy",
+            Self::AccessDenied => "access denied, the item may be private or friends-only",
+            Self::Timeout => "Steam timed out",
+            Self::Banned => "banned",
+            Self::ServiceUnavailable => "service unavailable",
+            Self::LimitExceeded => "limit exceeded",
+            Self::Revoked => "revoked",
+            Self::Expired => "expired",
+            Self::NoMatch => "no match",
+            Self::RateLimitExceeded => "rate limited",
+            Self::MissingDetails => "no details returned",
+            Self::Other(code) => return write!(f, "failed with EResult {}", code),
+        };
+        f.write_str(reason)
+    }
+}
+
+impl std::error::Error for EResult {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eresult() {
+        assert_eq!(EResult::from_code(1), None);
+        for code in [2, 9, 15, 17, 84, 123] {
+            assert_eq!(EResult::from_code(code).unwrap().code(), Some(code));
+        }
+        assert_eq!(EResult::MissingDetails.code(), None);
+        assert_eq!(EResult::from_code(9), Some(EResult::FileNotFound));
+        assert_eq!(EResult::from_code(123), Some(EResult::Other(123)));
+        assert_eq!(EResult::Other(123).to_string(), "failed with EResult 123");
+    }
+}
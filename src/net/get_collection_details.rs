@@ -1,11 +1,10 @@
 use std::collections::HashMap;
 
-use reqwest::header;
 use serde::{ser::SerializeStruct, Deserialize, Serialize};
 
 use crate::prelude::*;
 
-use super::{IterAdapter, Reqwest, Wrapper};
+use super::{batched, batching, IterAdapter, Reqwest, Wrapper};
 use serde_aux::prelude::*;
 
 #[derive(Debug)]
@@ -106,18 +105,41 @@ pub struct Child {
     pub filetype: u32,
 }
 
-const URL: &str = r"https://api.steampowered.com/ISteamRemoteStorage/GetCollectionDetails/v1/?";
+/// `filetype` of a child that is a regular workshop item.
+pub const FILETYPE_ITEM: u32 = 0;
+/// `filetype` of a child that is itself a collection linked from the parent.
+pub const FILETYPE_COLLECTION: u32 = 2;
 
+impl Child {
+    pub fn is_item(&self) -> bool {
+        self.filetype == FILETYPE_ITEM
+    }
+
+    pub fn is_collection(&self) -> bool {
+        self.filetype == FILETYPE_COLLECTION
+    }
+}
+
+const PATH: &str = "ISteamRemoteStorage/GetCollectionDetails/v1/";
+
+/// Retrieve the children of collections, in batches sent concurrently, see [`super::set_batch_size`].
 pub fn call(file_ids: impl Iterator<Item = u64> + Clone) -> Result<Response> {
+    let file_ids = file_ids.collect::<Vec<_>>();
+    let mut details = HashMap::with_capacity(file_ids.len());
+    let (batch_size, concurrency) = batching();
+    for response in batched(&file_ids, batch_size, concurrency, |batch| {
+        call_batch(batch.iter().copied())
+    })? {
+        details.extend(response.details);
+    }
+    Ok(Response { details })
+}
+
+fn call_batch(file_ids: impl Iterator<Item = u64> + Clone) -> Result<Response> {
     let payload = Payload::new(file_ids);
     let payload = serde_qs::to_string(&payload)?;
 
-    let client = Reqwest::client();
-    let response = client
-        .post(URL)
-        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
-        .body(payload)
-        .send()?;
+    let response = Reqwest::post_form(PATH, payload)?;
 
     let Wrapper { response } = response.json::<Wrapper<Response>>()?;
     Ok(response)
@@ -126,10 +148,34 @@ pub fn call(file_ids: impl Iterator<Item = u64> + Clone) -> Result<Response> {
 #[cfg(test)]
 mod tests {
     use super::call;
+    use crate::net::mock;
 
     #[test]
     fn test() {
+        mock::serve();
         let resp = call([1626860092, 2529002857].into_iter()).unwrap();
         println!("{:#?}", resp);
+
+        let children = resp.details[&1626860092].children.as_ref().unwrap();
+        assert_eq!(
+            children.iter().map(|c| c.file_id).collect::<Vec<_>>(),
+            [2529002857, 2824342092]
+        );
+        assert!(children.iter().all(|c| c.is_item()));
+        assert!(resp.details[&2529002857].children.is_none());
+    }
+
+    #[test]
+    fn test_linked_collection() {
+        mock::serve();
+        let resp = call([368330611].into_iter()).unwrap();
+        let linked = resp.details[&368330611]
+            .children
+            .iter()
+            .flatten()
+            .filter(|c| c.is_collection())
+            .map(|c| c.file_id)
+            .collect::<Vec<_>>();
+        assert_eq!(linked, [1541984105]);
     }
 }
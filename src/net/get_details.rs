@@ -0,0 +1,213 @@
+//! `IPublishedFileService/GetDetails`, the alternative to [`super::get_published_file_details`] selected with
+//! [`super::Backend::PublishedFileService`]. It needs an API key, and also returns the items each mod requires,
+//! its votes, and items that are private but accessible to the owner of the key.
+
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_aux::prelude::*;
+use serde_json::Value;
+
+use crate::prelude::*;
+
+use super::{
+    batched, batching,
+    get_collection_details::FILETYPE_COLLECTION,
+    get_published_file_details::{self, lenient_number, Metadata},
+    Reqwest, Wrapper,
+};
+
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    key: &'a str,
+    publishedfileids: &'a [FileId],
+    includetags: bool,
+    includechildren: bool,
+    includevotes: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(rename = "publishedfiledetails", default)]
+    details: Vec<Detail>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Detail {
+    #[serde(
+        rename = "publishedfileid",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    file_id: FileId,
+    result: u32,
+
+    #[serde(flatten)]
+    inner: Option<DetailInner>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DetailInner {
+    #[serde(
+        rename = "consumer_appid",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    app_id: FileId,
+    title: String,
+    #[serde(rename = "file_description", default)]
+    description: String,
+    #[serde(with = "ts_seconds")]
+    time_created: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    time_updated: DateTime<Utc>,
+    #[serde(default, deserialize_with = "lenient_number")]
+    file_type: Option<u32>,
+    #[serde(default, deserialize_with = "lenient_children")]
+    children: Vec<FileId>,
+    #[serde(default, deserialize_with = "lenient_votes")]
+    vote_data: Option<VoteData>,
+    #[serde(flatten)]
+    metadata: Metadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoteData {
+    #[serde(default, deserialize_with = "lenient_number")]
+    score: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_number")]
+    votes_up: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_number")]
+    votes_down: Option<u64>,
+}
+
+/// File ids of `[{"publishedfileid": "..."}]`, skipping malformed entries.
+fn lenient_children<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<FileId>, D::Error> {
+    let Value::Array(children) = Value::deserialize(deserializer)? else {
+        return Ok(Vec::new());
+    };
+    Ok(children
+        .into_iter()
+        .filter_map(|mut child| lenient_number(child.get_mut("publishedfileid")?.take()).ok()?)
+        .collect())
+}
+
+fn lenient_votes<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<VoteData>, D::Error> {
+    Ok(serde_json::from_value(Value::deserialize(deserializer)?).ok())
+}
+
+impl From<Detail> for get_published_file_details::Detail {
+    fn from(detail: Detail) -> Self {
+        let inner = detail.inner.map(|inner| {
+            let mut metadata = inner.metadata;
+            // the children of a collection are its items rather than required items
+            if inner.file_type != Some(FILETYPE_COLLECTION) {
+                metadata.required_items = inner.children;
+            }
+            if let Some(votes) = inner.vote_data {
+                metadata.score = votes.score;
+                metadata.votes_up = votes.votes_up;
+                metadata.votes_down = votes.votes_down;
+            }
+            get_published_file_details::DetailInner {
+                app_id: inner.app_id,
+                title: inner.title,
+                description: inner.description,
+                time_created: inner.time_created,
+                time_updated: inner.time_updated,
+                metadata,
+            }
+        });
+        Self {
+            file_id: detail.file_id,
+            result: detail.result,
+            inner,
+        }
+    }
+}
+
+const PATH: &str = "IPublishedFileService/GetDetails/v1/";
+
+/// Retrieve the details of workshop items with the API key `key`, in batches sent concurrently, see [`super::set_batch_size`].
+/// The details are in the order of `file_ids`, in the same form as those of [`get_published_file_details::call`].
+pub fn call(
+    key: &str,
+    file_ids: impl Iterator<Item = FileId> + Clone,
+) -> Result<get_published_file_details::Response> {
+    let file_ids = file_ids.collect::<Vec<_>>();
+    let mut merged = get_published_file_details::Response {
+        count: 0,
+        details: Vec::with_capacity(file_ids.len()),
+    };
+    let (batch_size, concurrency) = batching();
+    for response in batched(&file_ids, batch_size, concurrency, |batch| {
+        call_batch(key, batch)
+    })? {
+        merged.count += response.details.len();
+        merged
+            .details
+            .extend(response.details.into_iter().map(Into::into));
+    }
+    Ok(merged)
+}
+
+fn call_batch(key: &str, file_ids: &[FileId]) -> Result<Response> {
+    let payload = Payload {
+        key,
+        publishedfileids: file_ids,
+        includetags: true,
+        includechildren: true,
+        includevotes: true,
+    };
+    let query = serde_qs::to_string(&payload)?;
+
+    let response = Reqwest::get_query(PATH, query)?;
+
+    let Wrapper { response } = response.json::<Wrapper<Response>>()?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{eresult::EResult, mock};
+
+    #[test]
+    fn test() -> Result<()> {
+        mock::serve();
+        let resp = call(mock::API_KEY, [2824342092, 368330611, 1111].into_iter())?;
+
+        assert_eq!(resp.count, 3);
+        assert_eq!(
+            resp.details.iter().map(|d| d.error()).collect::<Vec<_>>(),
+            [None, None, Some(EResult::FileNotFound)]
+        );
+
+        let item = resp.details[0].inner.as_ref().unwrap();
+        assert_eq!(item.app_id, 294100);
+        assert_eq!(item.title, "Performance Fish");
+        assert_eq!(item.description, "Performance optimizations.");
+        assert_eq!(item.metadata.file_size, Some(4412893));
+        assert_eq!(item.metadata.tags, ["Mod", "1.4"]);
+        assert_eq!(item.metadata.banned, Some(false));
+        assert_eq!(item.metadata.required_items, [2009463077]);
+        assert_eq!(item.metadata.votes_up, Some(9000));
+
+        // the items of a collection are not required items
+        let collection = resp.details[1].inner.as_ref().unwrap();
+        assert!(collection.metadata.required_items.is_empty());
+
+        assert!(call("wrong", [2824342092].into_iter()).is_err());
+
+        // null or unexpected values of optional fields do not make an item invalid
+        let detail: Detail = serde_json::from_str(
+            r#"{"publishedfileid":"1","result":1,"consumer_appid":294100,"title":"","time_created":0,"time_updated":0,"file_type":null,"children":[{"publishedfileid":"5"},{}],"vote_data":null,"tags":null,"banned":false}"#,
+        )?;
+        let detail = get_published_file_details::Detail::from(detail);
+        assert_eq!(detail.error(), None);
+        assert_eq!(detail.inner.unwrap().metadata.required_items, [5]);
+        Ok(())
+    }
+}
@@ -1,12 +1,13 @@
 use chrono::serde::ts_seconds;
 use chrono::{DateTime, Utc};
-use reqwest::header;
-use serde::{ser::SerializeStruct, Deserialize, Serialize};
+use serde::{de::DeserializeOwned, ser::SerializeStruct, Deserialize, Serialize};
 use serde_aux::prelude::*;
+use serde_json::Value;
+use std::str::FromStr;
 
 use crate::prelude::*;
 
-use super::{IterAdapter, Reqwest, Wrapper};
+use super::{batched, batching, eresult::EResult, IterAdapter, Reqwest, Wrapper};
 
 #[derive(Debug)]
 struct Payload<I> {
@@ -59,8 +60,12 @@ pub struct Detail {
 }
 
 impl Detail {
-    pub fn is_valid_item(&self) -> bool {
-        self.result == 1 && self.inner.is_some()
+    /// Why the details of the item are missing, `None` for a valid item.
+    pub fn error(&self) -> Option<EResult> {
+        match EResult::from_code(self.result) {
+            None if self.inner.is_none() => Some(EResult::MissingDetails),
+            error => error,
+        }
     }
 }
 
@@ -77,20 +82,214 @@ pub struct DetailInner {
     pub time_created: DateTime<Utc>,
     #[serde(with = "ts_seconds")]
     pub time_updated: DateTime<Utc>,
+    #[serde(flatten)]
+    pub metadata: Metadata,
+}
+
+/// Details of an item beyond those needed to download it, each of which may be missing from a response.
+/// A value that is null or of an unexpected type counts as missing rather than making the whole item invalid.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Size of the item in bytes.
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub file_size: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_tags",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub tags: Vec<String>,
+    /// SteamID64 of the author.
+    #[serde(
+        default,
+        deserialize_with = "lenient_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub creator: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub preview_url: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub subscriptions: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub favorited: Option<u64>,
+    /// 0 is public, 1 friends-only, 2 private and 3 unlisted, see [`Metadata::visibility_name`].
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub visibility: Option<u32>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_flag",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub banned: Option<bool>,
+    /// Direct download link, only set for items stored outside of Steam's content system.
+    #[serde(
+        default,
+        deserialize_with = "lenient_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub file_url: Option<String>,
+    /// Items the item requires, only returned by [`super::get_details`].
+    #[serde(
+        default,
+        deserialize_with = "lenient_ids",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub required_items: Vec<FileId>,
+    /// Votes for and against the item, only returned by [`super::get_details`].
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub votes_up: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub votes_down: Option<u64>,
+    /// Share of votes in favour, between 0 and 1.
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub score: Option<f64>,
 }
 
-const URL: &str = "https://api.steampowered.com/ISteamRemoteStorage/GetPublishedFileDetails/v1/?";
+impl Metadata {
+    pub fn visibility_name(&self) -> Option<&'static str> {
+        match self.visibility? {
+            0 => Some("public"),
+            1 => Some("friends-only"),
+            2 => Some("private"),
+            3 => Some("unlisted"),
+            _ => None,
+        }
+    }
 
+    /// The file size in human-readable units, e.g. `4.2 MiB`.
+    pub fn size(&self) -> Option<String> {
+        let bytes = self.file_size?;
+        let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+        let mut size = bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < units.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        Some(match unit {
+            0 => format!("{} B", bytes),
+            _ => format!("{:.1} {}", size, units[unit]),
+        })
+    }
+}
+
+/// A number, which Steam sometimes sends as a string.
+pub(super) fn lenient_number<'de, D: serde::Deserializer<'de>, T: FromStr + DeserializeOwned>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s.trim().parse().ok(),
+        value @ Value::Number(_) => serde_json::from_value(value).ok(),
+        _ => None,
+    })
+}
+
+/// Empty strings stand for missing values in responses.
+pub(super) fn lenient_string<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) if !s.is_empty() => Some(s),
+        _ => None,
+    })
+}
+
+/// Flags come as 0 or 1, or as booleans.
+pub(super) fn lenient_flag<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<bool>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(flag) => Some(flag),
+        Value::Number(n) => n.as_u64().map(|n| n != 0),
+        Value::String(s) => match s.as_str() {
+            "1" | "true" => Some(true),
+            "0" | "false" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Tags come as `[{"tag": "Mod"}]`, only the names are kept.
+fn lenient_tags<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let Value::Array(tags) = Value::deserialize(deserializer)? else {
+        return Ok(Vec::new());
+    };
+    Ok(tags
+        .iter()
+        .filter_map(|tag| tag.get("tag")?.as_str().map(String::from))
+        .collect())
+}
+
+fn lenient_ids<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<FileId>, D::Error> {
+    let Value::Array(ids) = Value::deserialize(deserializer)? else {
+        return Ok(Vec::new());
+    };
+    Ok(ids
+        .into_iter()
+        .filter_map(|id| lenient_number(id).ok().flatten())
+        .collect())
+}
+
+const PATH: &str = "ISteamRemoteStorage/GetPublishedFileDetails/v1/";
+
+/// Retrieve the details of workshop items, in batches sent concurrently, see [`super::set_batch_size`].
+/// The details are in the order of `file_ids`.
 pub fn call(file_ids: impl Iterator<Item = u64> + Clone) -> Result<Response> {
+    let file_ids = file_ids.collect::<Vec<_>>();
+    let mut merged = Response {
+        count: 0,
+        details: Vec::with_capacity(file_ids.len()),
+    };
+    let (batch_size, concurrency) = batching();
+    for response in batched(&file_ids, batch_size, concurrency, |batch| {
+        call_batch(batch.iter().copied())
+    })? {
+        merged.count += response.count;
+        merged.details.extend(response.details);
+    }
+    Ok(merged)
+}
+
+fn call_batch(file_ids: impl Iterator<Item = u64> + Clone) -> Result<Response> {
     let payload = Payload::new(file_ids);
     let payload = serde_qs::to_string(&payload)?;
 
-    let client = Reqwest::client();
-    let response = client
-        .post(URL)
-        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
-        .body(payload)
-        .send()?;
+    let response = Reqwest::post_form(PATH, payload)?;
 
     let Wrapper { response } = response.json::<Wrapper<Response>>()?;
     Ok(response)
@@ -100,10 +299,74 @@ pub fn call(file_ids: impl Iterator<Item = u64> + Clone) -> Result<Response> {
 mod tests {
 
     use super::*;
+    use crate::net::mock;
 
     #[test]
     fn test() {
-        let resp = call([2824342092, 2529002857, 1111].into_iter()).unwrap();
+        mock::serve();
+        let resp = call([2824342092, 2529002857, 1111, 1112].into_iter()).unwrap();
         println!("{:#?}", resp);
+
+        assert_eq!(resp.count, 4);
+        let errors = resp
+            .details
+            .iter()
+            .map(|d| (d.file_id, d.error()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                (2824342092, None),
+                (2529002857, None),
+                (1111, Some(EResult::FileNotFound)),
+                (1112, Some(EResult::AccessDenied))
+            ]
+        );
+        let inner = resp.details[0].inner.as_ref().unwrap();
+        assert_eq!(inner.app_id, 294100);
+        assert_eq!(
+            inner.metadata,
+            Metadata {
+                file_size: Some(4412893),
+                tags: vec!["Mod".into(), "1.4".into()],
+                creator: Some("76561197990000005".into()),
+                preview_url: Some(
+                    "https://steamuserimages-a.akamaihd.net/ugc/19770394644/preview/".into()
+                ),
+                subscriptions: Some(381002),
+                favorited: Some(7210),
+                visibility: Some(0),
+                banned: Some(false),
+                file_url: None,
+                ..Default::default()
+            }
+        );
+        assert_eq!(inner.metadata.size().as_deref(), Some("4.2 MiB"));
+        assert_eq!(inner.metadata.visibility_name(), Some("public"));
+        assert_eq!(Metadata::default().size(), None);
+
+        // responses without the optional fields still parse
+        let detail: Detail = serde_json::from_str(
+            r#"{"publishedfileid":"1","result":1,"consumer_app_id":294100,"title":"","description":"","time_created":0,"time_updated":0,"file_size":"2048","tags":[]}"#,
+        )
+        .unwrap();
+        let metadata = &detail.inner.unwrap().metadata;
+        assert_eq!(metadata.file_size, Some(2048));
+        assert_eq!(metadata.banned, None);
+
+        // nor do null or unexpected values make a valid item invalid
+        let detail: Detail = serde_json::from_str(
+            r#"{"publishedfileid":"1","result":1,"consumer_app_id":294100,"title":"","description":"","time_created":0,"time_updated":0,"file_size":null,"tags":null,"creator":5,"banned":null,"visibility":"public","subscriptions":"many","favorited":-1,"file_url":{},"required_items":[1,"2",null]}"#,
+        )
+        .unwrap();
+        assert_eq!(detail.error(), None);
+        let metadata = &detail.inner.unwrap().metadata;
+        assert_eq!(
+            metadata,
+            &Metadata {
+                required_items: vec![1, 2],
+                ..Default::default()
+            }
+        );
     }
 }
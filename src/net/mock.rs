@@ -0,0 +1,144 @@
+//! A local stand-in for the Steam Web API, serving the recorded responses under `fixtures/` so tests run without network access.
+//!
+//! `fixtures/collections/<id>.json` holds the `collectiondetails` entry of a collection and
+//! `fixtures/files/<id>.json` holds the `publishedfiledetails` entry of a workshop item.
+//! Ids without a fixture are answered the way Steam answers unknown ids, with `result` 9.
+//!
+//! `IPublishedFileService/GetDetails` is answered from the same fixtures, renamed to the fields of that endpoint,
+//! with the children and votes only it returns taken from `fixtures/details/<id>.json`. It needs the key [`API_KEY`].
+
+use std::{fs, path::PathBuf, thread};
+
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::prelude::*;
+
+/// The only API key the stand-in server accepts.
+pub(crate) const API_KEY: &str = "test-key";
+
+static SERVER: OnceCell<String> = OnceCell::new();
+
+#[derive(Debug, Deserialize)]
+struct Query {
+    key: Option<String>,
+    publishedfileids: Vec<FileId>,
+}
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+fn entries(kind: &str, ids: &[FileId]) -> String {
+    ids.iter()
+        .map(|id| {
+            let path = fixture_dir().join(kind).join(format!("{}.json", id));
+            fs::read_to_string(path)
+                .unwrap_or_else(|_| format!(r#"{{"publishedfileid":"{}","result":9}}"#, id))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A `publishedfiledetails` fixture in the form `IPublishedFileService/GetDetails` returns it.
+fn details_entry(id: FileId) -> Value {
+    let read = |kind: &str| -> Option<Value> {
+        let path = fixture_dir().join(kind).join(format!("{}.json", id));
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    };
+    let Some(Value::Object(mut entry)) = read("files") else {
+        return json!({"publishedfileid": id.to_string(), "result": 9});
+    };
+
+    for (from, to) in [
+        ("consumer_app_id", "consumer_appid"),
+        ("creator_app_id", "creator_appid"),
+        ("description", "file_description"),
+    ] {
+        if let Some(value) = entry.remove(from) {
+            entry.insert(to.into(), value);
+        }
+    }
+    let banned = entry.get("banned").and_then(Value::as_u64) == Some(1);
+    entry.insert("banned".into(), banned.into());
+    if let Some(size) = entry.get("file_size").and_then(Value::as_u64) {
+        entry.insert("file_size".into(), size.to_string().into());
+    }
+
+    let collection = read("collections");
+    entry.insert(
+        "file_type".into(),
+        if collection.is_some() { 2 } else { 0 }.into(),
+    );
+    if let Some(children) = collection.and_then(|c| c.get("children").cloned()) {
+        entry.insert("children".into(), children);
+    }
+    if let Some(Value::Object(extra)) = read("details") {
+        entry.extend(extra);
+    }
+    Value::Object(entry)
+}
+
+fn respond(url: &str, body: &str) -> Option<String> {
+    if url.contains("IPublishedFileService/GetDetails") {
+        let (_, query) = url.split_once('?')?;
+        let Query {
+            key,
+            publishedfileids,
+        } = serde_qs::from_str(query).ok()?;
+        if key.as_deref() != Some(API_KEY) {
+            return None;
+        }
+        let details = publishedfileids
+            .into_iter()
+            .map(details_entry)
+            .collect::<Vec<_>>();
+        return Some(json!({"response": {"publishedfiledetails": details}}).to_string());
+    }
+
+    let Query {
+        publishedfileids, ..
+    } = serde_qs::from_str(body).ok()?;
+    let (kind, field) = if url.contains("GetCollectionDetails") {
+        ("collections", "collectiondetails")
+    } else if url.contains("GetPublishedFileDetails") {
+        ("files", "publishedfiledetails")
+    } else {
+        return None;
+    };
+
+    Some(format!(
+        r#"{{"response":{{"result":1,"resultcount":{},"{}":[{}]}}}}"#,
+        publishedfileids.len(),
+        field,
+        entries(kind, &publishedfileids)
+    ))
+}
+
+/// Start the stand-in server once per test binary and point the API client at it.
+pub(crate) fn serve() {
+    SERVER.get_or_init(|| {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body);
+
+                let response = match respond(request.url(), &body) {
+                    Some(json) => Response::from_string(json).with_header(
+                        Header::from_bytes("Content-Type", "application/json").unwrap(),
+                    ),
+                    None => Response::from_string("Not Found").with_status_code(404),
+                };
+                let _ = request.respond(response);
+            }
+        });
+
+        super::set_base_url(url.clone()).unwrap();
+        url
+    });
+}
@@ -1,21 +1,249 @@
-use std::cell::Cell;
+use std::{
+    cell::Cell,
+    fmt::Display,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
 
 use once_cell::sync::OnceCell;
-use reqwest::blocking::Client;
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::prelude::FileId;
+use crate::prelude::*;
 
+pub mod eresult;
 pub mod get_collection_details;
+pub mod get_details;
 pub mod get_published_file_details;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod retry;
+
+use retry::{send_with_retry, RetryPolicy};
+
+/// Base URL of the Steam Web API used when no other base URL has been set.
+pub const DEFAULT_BASE_URL: &str = "https://api.steampowered.com";
+
+/// Number of file ids sent per request unless set otherwise with [`set_batch_size`].
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+/// Number of requests in flight at once unless set otherwise with [`set_concurrency`].
+pub const DEFAULT_CONCURRENCY: usize = 4;
+/// Seconds a request may take unless set otherwise with [`set_timeout`].
+pub const DEFAULT_TIMEOUT: u64 = 30;
+/// Number of times a failed request is re-sent unless set otherwise with [`set_retries`].
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Environment variable the API key is read from when it is not given with `--api-key`.
+pub const API_KEY_VAR: &str = "STEAM_API_KEY";
+
+/// The endpoint the details of workshop items are retrieved from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// `ISteamRemoteStorage/GetPublishedFileDetails`, which needs no API key, see [`get_published_file_details`].
+    #[default]
+    RemoteStorage,
+    /// `IPublishedFileService/GetDetails`, which needs an API key, see [`get_details`].
+    PublishedFileService,
+}
+
+impl Backend {
+    pub const NAMES: &'static [&'static str] = &["remote-storage", "published-file-service"];
+}
+
+impl FromStr for Backend {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "remote-storage" => Ok(Self::RemoteStorage),
+            "published-file-service" => Ok(Self::PublishedFileService),
+            _ => Err(color_eyre::eyre::eyre!(
+                "unknown API backend '{}', expected one of {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Backend {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Backend::RemoteStorage => "remote-storage",
+            Backend::PublishedFileService => "published-file-service",
+        })
+    }
+}
 
 struct Reqwest;
 static CLIENT: OnceCell<Client> = OnceCell::new();
+static BASE_URL: OnceCell<String> = OnceCell::new();
+static API_KEY: OnceCell<String> = OnceCell::new();
+static BACKEND: OnceCell<Backend> = OnceCell::new();
+static BATCH_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_BATCH_SIZE);
+static CONCURRENCY: AtomicUsize = AtomicUsize::new(DEFAULT_CONCURRENCY);
+static TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT);
+static RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_RETRIES);
 
 impl Reqwest {
     fn client() -> &'static Client {
         CLIENT.get_or_init(Client::new)
     }
+
+    fn url(path: &str) -> String {
+        let base = BASE_URL.get_or_init(|| DEFAULT_BASE_URL.into());
+        format!("{}/{}", base.trim_end_matches('/'), path)
+    }
+
+    /// Send a request to an API endpoint, with the configured timeout and retries.
+    fn send(path: &str, request: impl Fn(&Client, &str) -> RequestBuilder) -> Result<Response> {
+        let url = Self::url(path);
+        let timeout = Duration::from_secs(TIMEOUT.load(Ordering::Relaxed));
+        let policy = RetryPolicy {
+            attempts: RETRIES.load(Ordering::Relaxed),
+            delay: Duration::from_secs(1),
+        };
+        send_with_retry(&policy, || request(Self::client(), &url).timeout(timeout))
+    }
+
+    /// POST a form to an API endpoint.
+    fn post_form(path: &str, form: String) -> Result<Response> {
+        Self::send(path, |client, url| {
+            client
+                .post(url)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(form.clone())
+        })
+    }
+
+    /// GET an API endpoint with a query string.
+    fn get_query(path: &str, query: String) -> Result<Response> {
+        Self::send(path, |client, url| client.get(format!("{}?{}", url, query)))
+    }
+}
+
+/// Point all subsequent API calls at `base_url` instead of [`DEFAULT_BASE_URL`], e.g. a local mirror or stand-in server.
+/// The base URL can only be set once, before the first API call.
+pub fn set_base_url(base_url: impl Into<String>) -> Result<()> {
+    BASE_URL.set(base_url.into()).map_err(|url| {
+        color_eyre::eyre::eyre!("API base URL already set, cannot change it to {}", url)
+    })
+}
+
+/// Authenticate API calls with a Steam Web API key, needed by [`Backend::PublishedFileService`].
+/// The key can only be set once, before the first API call.
+pub fn set_api_key(key: impl Into<String>) -> Result<()> {
+    API_KEY
+        .set(key.into())
+        .map_err(|_| color_eyre::eyre::eyre!("API key already set"))
+}
+
+/// Retrieve the details of workshop items from `backend` instead of [`Backend::RemoteStorage`].
+/// The backend can only be set once, before the first API call.
+pub fn set_backend(backend: Backend) -> Result<()> {
+    BACKEND.set(backend).map_err(|backend| {
+        color_eyre::eyre::eyre!("API backend already set, cannot change it to {}", backend)
+    })
+}
+
+/// Retrieve the details of workshop items from the backend set with [`set_backend`]. The details are in the order of `file_ids`.
+pub fn file_details(
+    file_ids: impl Iterator<Item = FileId> + Clone,
+) -> Result<get_published_file_details::Response> {
+    match BACKEND.get().copied().unwrap_or_default() {
+        Backend::RemoteStorage => get_published_file_details::call(file_ids),
+        Backend::PublishedFileService => {
+            let key = API_KEY.get().ok_or_else(|| {
+                color_eyre::eyre::eyre!(
+                    "the {} API backend needs an API key, set it with --api-key or {}",
+                    Backend::PublishedFileService,
+                    API_KEY_VAR
+                )
+            })?;
+            get_details::call(key, file_ids)
+        }
+    }
+}
+
+/// Give up on requests that take longer than `timeout`.
+pub fn set_timeout(timeout: Duration) {
+    TIMEOUT.store(timeout.as_secs().max(1), Ordering::Relaxed);
+}
+
+/// Re-send requests that timed out, lost their connection, were rate limited or hit a server error up to `retries` times.
+pub fn set_retries(retries: u32) {
+    RETRIES.store(retries, Ordering::Relaxed);
+}
+
+/// Split the file ids of each API call into requests of at most `batch_size` ids.
+pub fn set_batch_size(batch_size: usize) {
+    BATCH_SIZE.store(batch_size.max(1), Ordering::Relaxed);
+}
+
+/// Send at most `concurrency` requests of an API call at once.
+pub fn set_concurrency(concurrency: usize) {
+    CONCURRENCY.store(concurrency.max(1), Ordering::Relaxed);
+}
+
+/// The batch size and concurrency set with [`set_batch_size`] and [`set_concurrency`], for [`batched`].
+fn batching() -> (usize, usize) {
+    (
+        BATCH_SIZE.load(Ordering::Relaxed),
+        CONCURRENCY.load(Ordering::Relaxed),
+    )
+}
+
+/// Call `f` on batches of `batch_size` of `ids`, running up to `concurrency` of them at once. Results are returned in batch order,
+/// and the first error is returned once all batches are done.
+fn batched<T: Send>(
+    ids: &[FileId],
+    batch_size: usize,
+    concurrency: usize,
+    f: impl Fn(&[FileId]) -> Result<T> + Sync,
+) -> Result<Vec<T>> {
+    let batches = ids.chunks(batch_size.max(1)).collect::<Vec<_>>();
+    let workers = concurrency.min(batches.len());
+    if workers <= 1 {
+        return batches.into_iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..batches.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(batch) = batches.get(i) else {
+                    break;
+                };
+                let result = f(batch);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every batch is processed"))
+        .collect()
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,3 +268,44 @@ impl<I: Iterator<Item = FileId> + Clone> Serialize for IterAdapter<I> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_batched() -> Result<()> {
+        let ids = (1..=10).collect::<Vec<FileId>>();
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        let batches = ids.chunks(3).collect::<Vec<_>>();
+        let workers = 2;
+        let call = |batch: &[FileId]| -> Result<Vec<FileId>> {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(batch.to_vec())
+        };
+
+        assert_eq!(batched(&ids, 3, workers, call)?, batches);
+        assert!(peak.load(Ordering::SeqCst) <= workers);
+        assert!(peak.load(Ordering::SeqCst) > 1);
+
+        peak.store(0, Ordering::SeqCst);
+        assert_eq!(batched(&ids, 3, 1, call)?, batches);
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+
+        let failing = batched(&ids, 3, workers, |batch| {
+            if batch.contains(&5) {
+                Err(color_eyre::eyre::eyre!("batch failed"))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(failing.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+//! Retrying API requests that failed for reasons that may go away: timeouts, dropped connections,
+//! rate limiting (429) and server errors (5xx).
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    blocking::{RequestBuilder, Response},
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
+
+use crate::prelude::*;
+
+/// The longest `Retry-After` that is honoured, longer ones are cut to this.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of times a failed request is re-sent.
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for every further retry.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter: a random delay between half and all of `delay` doubled `attempt` times,
+    /// so concurrent requests failing together do not retry in lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.delay * 2u32.saturating_pow(attempt);
+        let jitter = RandomState::new().build_hasher().finish() % 1000;
+        delay / 2 + delay / 2 * jitter as u32 / 1000
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Timeouts and connection or transfer failures, but not errors in building the request such as a malformed URL.
+fn is_retryable_error(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || e.is_body()
+}
+
+/// Drop the query from the URL of an error, as it may hold the API key.
+fn redact(mut e: reqwest::Error) -> reqwest::Error {
+    if let Some(url) = e.url_mut() {
+        url.set_query(None);
+    }
+    e
+}
+
+/// The delay asked for by a `Retry-After` header, either in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        }
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+/// Send the request built by `request`, re-building and re-sending it as `policy` allows when it fails in a way that may go away.
+/// A `Retry-After` header on the failed response takes precedence over the backoff of `policy`.
+pub fn send_with_retry(
+    policy: &RetryPolicy,
+    request: impl Fn() -> RequestBuilder,
+) -> Result<Response> {
+    let mut attempt = 0;
+    loop {
+        let (error, wait) = match request().send() {
+            Ok(response) if is_retryable_status(response.status()) => {
+                let wait = retry_after(response.headers());
+                let mut url = response.url().clone();
+                url.set_query(None);
+                let error = color_eyre::eyre::eyre!("{} responded with {}", url, response.status());
+                (error, wait)
+            }
+            Ok(response) => return response.error_for_status().map_err(|e| redact(e).into()),
+            Err(e) if is_retryable_error(&e) => (redact(e).into(), None),
+            Err(e) => return Err(redact(e).into()),
+        };
+
+        if attempt >= policy.attempts {
+            return Err(error.wrap_err(format!("giving up after {} attempts", attempt + 1)));
+        }
+        let wait = wait.unwrap_or_else(|| policy.backoff(attempt));
+        println!("{}, retrying in {:.1}s", error, wait.as_secs_f32());
+        thread::sleep(wait);
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::blocking::Client;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tiny_http::{Header, Response, Server};
+
+    /// A server answering with `statuses` in turn, then with 200 OK, and counting the requests it got.
+    fn flaky_server(statuses: Vec<(u16, Option<&'static str>)>) -> (String, Arc<AtomicUsize>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", server.server_addr().to_ip().unwrap());
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let requests = count.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let i = requests.fetch_add(1, Ordering::SeqCst);
+                let response = match statuses.get(i) {
+                    Some(&(status, retry_after)) => {
+                        let response = Response::from_string("busy").with_status_code(status);
+                        match retry_after {
+                            Some(value) => response
+                                .with_header(Header::from_bytes("Retry-After", value).unwrap()),
+                            None => response,
+                        }
+                    }
+                    None => Response::from_string("ok"),
+                };
+                let _ = request.respond(response);
+            }
+        });
+        (url, count)
+    }
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        attempts: 3,
+        delay: Duration::from_millis(1),
+    };
+
+    #[test]
+    fn test_retry() -> Result<()> {
+        let client = Client::new();
+        let (url, count) = flaky_server(vec![(503, None), (429, Some("0")), (502, None)]);
+        let response = send_with_retry(&POLICY, || client.get(&url))?;
+        assert_eq!(response.text()?, "ok");
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+
+        let (url, count) = flaky_server(vec![(500, None); 4]);
+        assert!(send_with_retry(&POLICY, || client.get(&url)).is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+
+        // client errors other than 429 are not retried
+        let (url, count) = flaky_server(vec![(404, None)]);
+        assert!(send_with_retry(&POLICY, || client.get(&url)).is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // the query, which may hold the API key, is left out of errors
+        let (url, _) = flaky_server(vec![(403, None)]);
+        let error =
+            send_with_retry(&POLICY, || client.get(&url).query(&[("key", "secret")])).unwrap_err();
+        assert!(!format!("{:?}", error).contains("secret"));
+
+        // a request that cannot be built fails for good
+        let attempts = AtomicUsize::new(0);
+        let result = send_with_retry(&POLICY, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            client.get("not a url")
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeout() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", server.server_addr().to_ip().unwrap());
+        // accept requests without ever answering them
+        thread::spawn(move || {
+            let _pending = server.incoming_requests().collect::<Vec<_>>();
+        });
+
+        let client = Client::new();
+        let policy = RetryPolicy {
+            attempts: 1,
+            ..POLICY
+        };
+        let result = send_with_retry(&policy, || {
+            client.get(&url).timeout(Duration::from_millis(100))
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.insert(RETRY_AFTER, "86400".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(MAX_RETRY_AFTER));
+
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        let date = (Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        headers.insert(RETRY_AFTER, date.parse().unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_secs(2),
+        };
+        for attempt in 0..3 {
+            let full = Duration::from_secs(2 << attempt);
+            let wait = policy.backoff(attempt);
+            assert!(wait >= full / 2 && wait <= full, "{:?}", wait);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+mod report;
+
+pub use report::{parse_line, run_captured, DownloadOutcome, DownloadReport, ItemResult};
+
+use crate::prelude::*;
+use color_eyre::eyre::WrapErr;
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+/// How often failed downloads are re-issued, and how long to wait before the first retry.
+/// The delay doubles with every further attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    pub attempts: u32,
+    pub delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: 0,
+            delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Retry {
+    /// The delay before retry number `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+/// Builder for a SteamCMD script: an optional install directory, a login and a list of workshop downloads.
+/// The script is either passed as command-line arguments through [`SteamCmd::build`],
+/// or written to a file for `+runscript` through [`SteamCmd::write_script`].
+#[derive(Debug, Clone)]
+pub struct SteamCmd {
+    program: OsString,
+    install_dir: Option<PathBuf>,
+    username: String,
+    downloads: Vec<(FileId, FileId)>,
+    seen: HashSet<FileId>,
+}
+
+impl Default for SteamCmd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SteamCmd {
+    pub fn new() -> Self {
+        Self {
+            program: "steamcmd".into(),
+            install_dir: None,
+            username: "anonymous".into(),
+            downloads: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Set the SteamCMD executable to run, `steamcmd` from PATH by default.
+    pub fn program(&mut self, program: impl Into<OsString>) -> &mut Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Set the path passed to `force_install_dir`.
+    pub fn install_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.install_dir = Some(path.into());
+        self
+    }
+
+    /// Set the Steam username passed to `login`, `anonymous` by default.
+    pub fn login(&mut self, username: impl Into<String>) -> &mut Self {
+        self.username = username.into();
+        self
+    }
+
+    /// Queue a `workshop_download_item`. Items already queued are ignored, returns whether the item was added.
+    pub fn download(&mut self, app_id: FileId, file_id: FileId) -> bool {
+        let added = self.seen.insert(file_id);
+        if added {
+            self.downloads.push((app_id, file_id));
+        }
+        added
+    }
+
+    pub fn get_install_dir(&self) -> Option<&Path> {
+        self.install_dir.as_deref()
+    }
+
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+
+    /// The queued `(app_id, file_id)` pairs in download order.
+    pub fn get_downloads(&self) -> &[(FileId, FileId)] {
+        &self.downloads
+    }
+
+    /// Assemble the `steamcmd` command, passing every script line as a `+command` argument.
+    pub fn build(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        if let Some(path) = self.install_dir.as_deref() {
+            cmd.arg("+force_install_dir");
+            cmd.arg(path);
+        }
+
+        cmd.arg("+login").arg(&self.username);
+
+        for (app_id, file_id) in self.downloads.iter() {
+            cmd.arg(format!("+workshop_download_item {} {}", app_id, file_id));
+        }
+
+        cmd.arg("+quit");
+        cmd
+    }
+
+    /// Render the same commands as [`SteamCmd::build`] as a script for `+runscript`, one command per line.
+    /// SteamCMD has no way to escape quotes, so a download location containing `"` or a line break is rejected.
+    pub fn script(&self) -> Result<String> {
+        let mut script = String::new();
+        if let Some(path) = self.install_dir.as_deref() {
+            let path = path.display().to_string();
+            if path.contains(['"', '\r', '\n']) {
+                return Err(color_eyre::eyre::eyre!(
+                    "the download location {:?} cannot be written to a SteamCMD script, as it contains a quote or line break",
+                    path
+                ));
+            }
+            script.push_str(&format!("force_install_dir \"{}\"\n", path));
+        }
+
+        script.push_str(&format!("login {}\n", self.username));
+
+        for (app_id, file_id) in self.downloads.iter() {
+            script.push_str(&format!("workshop_download_item {} {}\n", app_id, file_id));
+        }
+
+        script.push_str("quit\n");
+        Ok(script)
+    }
+
+    /// Write [`SteamCmd::script`] to `path`.
+    pub fn write_script(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.script()?)?;
+        Ok(())
+    }
+
+    /// Run `cmd`, built from this script through [`SteamCmd::build`] or [`SteamCmd::runscript`],
+    /// and collect the result of each queued download from its output.
+    pub fn execute(&self, cmd: &mut Command) -> Result<DownloadReport> {
+        let (status, output) = run_captured(cmd).wrap_err("failed to run steamcmd")?;
+        let report = DownloadReport::from_output(&self.downloads, &output);
+        if !status.success() && report.is_success() {
+            return Err(color_eyre::eyre::eyre!("steamcmd exited with {}", status));
+        }
+        Ok(report)
+    }
+
+    /// Run `cmd` like [`SteamCmd::execute`], then re-issue the downloads that failed, up to `retry.attempts` times.
+    /// Retries pass only the failed items as command-line arguments, whether `cmd` was built from a script file or not.
+    pub fn execute_with_retry(&self, cmd: &mut Command, retry: &Retry) -> Result<DownloadReport> {
+        let mut report = self.execute(cmd)?;
+
+        for attempt in 1..=retry.attempts {
+            if report.is_success() {
+                break;
+            }
+
+            let failed = self.failed_only(&report);
+            let delay = retry.backoff(attempt);
+            println!(
+                "\nRetrying {} failed downloads in {}s (attempt {} of {})",
+                failed.downloads.len(),
+                delay.as_secs_f32(),
+                attempt,
+                retry.attempts
+            );
+            thread::sleep(delay);
+
+            let retried = failed.execute(&mut failed.build())?;
+            report.merge(retried);
+        }
+
+        Ok(report)
+    }
+
+    /// A copy of this script that only downloads the items that failed in `report`.
+    pub fn failed_only(&self, report: &DownloadReport) -> Self {
+        let mut script = Self {
+            downloads: Vec::new(),
+            seen: HashSet::new(),
+            ..self.clone()
+        };
+        for item in report.failed() {
+            script.download(item.app_id, item.file_id);
+        }
+        script
+    }
+
+    /// Assemble a `steamcmd +runscript <path>` command. SteamCMD resolves relative paths against its own directory,
+    /// so `path` should be absolute.
+    pub fn runscript(&self, path: impl AsRef<Path>) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.arg("+runscript").arg(path.as_ref());
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempPath;
+
+    #[test]
+    fn test_build() {
+        let mut steamcmd = SteamCmd::new();
+        steamcmd.install_dir("/srv/mods").login("someone");
+        assert!(steamcmd.download(294100, 818773962));
+        assert!(steamcmd.download(294100, 2009463077));
+        assert!(!steamcmd.download(294100, 818773962));
+
+        let cmd = steamcmd.build();
+        let args = cmd
+            .get_args()
+            .filter_map(|arg| arg.to_str())
+            .collect::<Vec<_>>();
+        assert_eq!(cmd.get_program(), "steamcmd");
+        assert_eq!(
+            args,
+            [
+                "+force_install_dir",
+                "/srv/mods",
+                "+login",
+                "someone",
+                "+workshop_download_item 294100 818773962",
+                "+workshop_download_item 294100 2009463077",
+                "+quit"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_script() -> Result<()> {
+        let mut steamcmd = SteamCmd::new();
+        steamcmd.install_dir("/srv/my mods");
+        steamcmd.download(294100, 818773962);
+        steamcmd.download(294100, 2009463077);
+
+        assert_eq!(
+            steamcmd.script()?,
+            "force_install_dir \"/srv/my mods\"\n\
+             login anonymous\n\
+             workshop_download_item 294100 818773962\n\
+             workshop_download_item 294100 2009463077\n\
+             quit\n"
+        );
+
+        let cmd = steamcmd.runscript("/tmp/swd.txt");
+        let args = cmd
+            .get_args()
+            .filter_map(|arg| arg.to_str())
+            .collect::<Vec<_>>();
+        assert_eq!(args, ["+runscript", "/tmp/swd.txt"]);
+
+        steamcmd.install_dir("/srv/\"quoted\" mods");
+        assert!(steamcmd.script().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry = Retry {
+            attempts: 3,
+            delay: Duration::from_secs(5),
+        };
+        assert_eq!(retry.backoff(1), Duration::from_secs(5));
+        assert_eq!(retry.backoff(2), Duration::from_secs(10));
+        assert_eq!(retry.backoff(3), Duration::from_secs(20));
+    }
+
+    /// A stand-in for steamcmd that fails each item the first time it is asked for, and succeeds afterwards.
+    #[cfg(unix)]
+    fn flaky_steamcmd(dir: &Path) -> Result<PathBuf> {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join("steamcmd");
+        fs::write(
+            &path,
+            r#"#!/bin/sh
+dir=$(dirname "$0")
+for arg in "$@"; do
+    case "$arg" in
+        "+workshop_download_item "*)
+            id=${arg##* }
+            if [ -e "$dir/$id" ]; then
+                echo "Success. Downloaded item $id to \"$dir/$id\" (1 bytes)"
+            else
+                touch "$dir/$id"
+                echo "ERROR! Download item $id failed (Timeout)."
+            fi
+            ;;
+    esac
+done
+"#,
+        )?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        Ok(path)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_with_retry() -> Result<()> {
+        let dir = TempPath::new("retry");
+        let program = flaky_steamcmd(&dir)?;
+
+        let mut steamcmd = SteamCmd::new();
+        steamcmd.program(&program);
+        steamcmd.download(294100, 818773962);
+        steamcmd.download(294100, 2009463077);
+        fs::write(dir.join("818773962"), "")?;
+
+        let no_retry = Retry {
+            attempts: 0,
+            delay: Duration::ZERO,
+        };
+        let report = steamcmd.execute_with_retry(&mut steamcmd.build(), &no_retry)?;
+        assert_eq!(
+            report.failed().map(|i| i.file_id).collect::<Vec<_>>(),
+            [2009463077]
+        );
+        assert_eq!(
+            steamcmd.failed_only(&report).get_downloads(),
+            [(294100, 2009463077)]
+        );
+
+        fs::remove_file(dir.join("2009463077"))?;
+        let retry = Retry {
+            attempts: 2,
+            delay: Duration::ZERO,
+        };
+        let report = steamcmd.execute_with_retry(&mut steamcmd.build(), &retry)?;
+        assert!(report.is_success());
+        assert_eq!(report.items.len(), 2);
+        Ok(())
+    }
+}
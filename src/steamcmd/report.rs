@@ -0,0 +1,208 @@
+use crate::prelude::*;
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+    process::{Command, ExitStatus, Stdio},
+};
+
+/// Result of a single `workshop_download_item`, as reported by SteamCMD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadOutcome {
+    /// `Success. Downloaded item <id> to "<path>" (<n> bytes)`
+    Success(PathBuf),
+    /// `ERROR! Download item <id> failed (<reason>).`
+    Failure(String),
+}
+
+impl DownloadOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Success(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemResult {
+    pub app_id: FileId,
+    pub file_id: FileId,
+    pub outcome: DownloadOutcome,
+}
+
+/// Per-item results of a SteamCMD run, in download order.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadReport {
+    pub items: Vec<ItemResult>,
+}
+
+const SUCCESS: &str = "Success. Downloaded item ";
+const ERROR: &str = "ERROR! Download item ";
+
+/// Parse a line of SteamCMD output into the item it reports on, if any.
+pub fn parse_line(line: &str) -> Option<(FileId, DownloadOutcome)> {
+    if let Some(pos) = line.find(SUCCESS) {
+        let (file_id, rest) = line[pos + SUCCESS.len()..].split_once(' ')?;
+        let path = rest.strip_prefix("to ")?.trim();
+        let path = match path.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"')?.0,
+            None => path.split_once(" (").map_or(path, |(path, _)| path),
+        };
+        Some((file_id.parse().ok()?, DownloadOutcome::Success(path.into())))
+    } else if let Some(pos) = line.find(ERROR) {
+        let (file_id, rest) = line[pos + ERROR.len()..].split_once(' ')?;
+        let reason = rest.strip_prefix("failed")?.trim().trim_end_matches('.');
+        let reason = reason
+            .strip_prefix('(')
+            .and_then(|r| r.strip_suffix(')'))
+            .unwrap_or(reason);
+        Some((
+            file_id.parse().ok()?,
+            DownloadOutcome::Failure(reason.into()),
+        ))
+    } else {
+        None
+    }
+}
+
+impl DownloadReport {
+    /// Match the queued `downloads` against the results found in `output`.
+    /// Items SteamCMD never reported on count as failed.
+    pub fn from_output(downloads: &[(FileId, FileId)], output: &str) -> Self {
+        let results = output.lines().filter_map(parse_line).collect::<Vec<_>>();
+        let items = downloads
+            .iter()
+            .map(|&(app_id, file_id)| {
+                let outcome = results
+                    .iter()
+                    .rev()
+                    .find(|(id, _)| *id == file_id)
+                    .map(|(_, outcome)| outcome.clone())
+                    .unwrap_or_else(|| DownloadOutcome::Failure("no result reported".into()));
+                ItemResult {
+                    app_id,
+                    file_id,
+                    outcome,
+                }
+            })
+            .collect();
+        Self { items }
+    }
+
+    pub fn succeeded(&self) -> impl Iterator<Item = &ItemResult> {
+        self.items.iter().filter(|item| item.outcome.is_success())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &ItemResult> {
+        self.items.iter().filter(|item| !item.outcome.is_success())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.items.iter().all(|item| item.outcome.is_success())
+    }
+
+    /// Update the outcome of every item `other` reports on, e.g. with the results of a retry.
+    pub fn merge(&mut self, other: DownloadReport) {
+        for result in other.items {
+            match self.items.iter_mut().find(|i| i.file_id == result.file_id) {
+                Some(item) => *item = result,
+                None => self.items.push(result),
+            }
+        }
+    }
+}
+
+/// Run `cmd`, echoing its standard output as it arrives while also capturing it.
+/// Output is forwarded unbuffered so that interactive prompts, e.g. for a password or Steam Guard code, still show up.
+pub fn run_captured(cmd: &mut Command) -> Result<(ExitStatus, String)> {
+    let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+
+    let mut captured = Vec::new();
+    let mut buf = [0; 4096];
+    let mut out = std::io::stdout();
+    loop {
+        let n = stdout.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n])?;
+        out.flush()?;
+        captured.extend_from_slice(&buf[..n]);
+    }
+
+    let status = child.wait()?;
+    Ok((status, String::from_utf8_lossy(&captured).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "\
+Redirecting stderr to '/home/steam/Steam/logs/stderr.txt'
+Loading Steam API...OK
+Connecting anonymously to Steam Public...OK
+Waiting for client config...OK
+Waiting for user info...OK
+Downloading item 818773962 ...
+Success. Downloaded item 818773962 to \"/srv/mods/steamapps/workshop/content/294100/818773962\" (2587990 bytes)
+Downloading item 2009463077 ...
+ERROR! Download item 2009463077 failed (Timeout).
+";
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line(
+                "Success. Downloaded item 818773962 to \"/srv/my mods/818773962\" (2587990 bytes) "
+            ),
+            Some((
+                818773962,
+                DownloadOutcome::Success("/srv/my mods/818773962".into())
+            ))
+        );
+        assert_eq!(
+            parse_line("ERROR! Download item 2009463077 failed (Access Denied)."),
+            Some((2009463077, DownloadOutcome::Failure("Access Denied".into())))
+        );
+        assert_eq!(parse_line("Downloading item 818773962 ..."), None);
+    }
+
+    #[test]
+    fn test_report() {
+        let report = DownloadReport::from_output(
+            &[
+                (294100, 818773962),
+                (294100, 2009463077),
+                (294100, 761421485),
+            ],
+            OUTPUT,
+        );
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.succeeded().map(|i| i.file_id).collect::<Vec<_>>(),
+            [818773962]
+        );
+        assert_eq!(
+            report
+                .failed()
+                .map(|i| (i.file_id, i.outcome.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (2009463077, DownloadOutcome::Failure("Timeout".into())),
+                (
+                    761421485,
+                    DownloadOutcome::Failure("no result reported".into())
+                )
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_captured() -> Result<()> {
+        let (status, output) = run_captured(Command::new("printf").arg(OUTPUT))?;
+        assert!(status.success());
+        assert_eq!(output, OUTPUT);
+        Ok(())
+    }
+}
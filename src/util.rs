@@ -1,5 +1,6 @@
 use std::{ffi::OsString, fmt::Display, process::Command};
 
+#[allow(dead_code)]
 pub(crate) struct OsStrBuf<'a> {
     pub(crate) inner: &'a mut OsString,
 }
@@ -53,6 +54,46 @@ macro_rules! os_str_format {
 #[allow(unused_imports)]
 pub(crate) use os_str_format;
 
+/// A path in the temporary directory unique to the test process and `name`, removed when dropped,
+/// so that concurrent test runs do not collide and failed tests leave nothing behind.
+#[cfg(test)]
+pub(crate) struct TempPath(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("swd_test_{}_{}", std::process::id(), name));
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempPath {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempPath {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        } else {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -0,0 +1,460 @@
+use crate::net::{
+    self,
+    eresult::EResult,
+    get_collection_details,
+    get_published_file_details::{DetailInner, Metadata},
+};
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use dialoguer::Input;
+use itertools::Itertools;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    str::FromStr,
+};
+
+/// Extract the file ID from a bare file ID or a workshop URL, such as
+/// `https://steamcommunity.com/sharedfiles/filedetails/?id=<id>`, `https://steamcommunity.com/workshop/filedetails/?id=<id>`,
+/// `steam://url/CommunityFilePage/<id>` or `steam://openurl/<url>`.
+pub fn parse_file_id(s: &str) -> Result<FileId> {
+    let s = s.trim();
+    if let Ok(id) = s.parse() {
+        return Ok(id);
+    }
+
+    let invalid = || {
+        color_eyre::eyre::eyre!(
+            "unrecognised workshop item '{}', expected a file ID or a workshop URL such as https://steamcommunity.com/sharedfiles/filedetails/?id=<file ID>",
+            s
+        )
+    };
+
+    let url = s.strip_prefix("steam://openurl/").unwrap_or(s);
+    if let Some(id) = url.strip_prefix("steam://url/CommunityFilePage/") {
+        return id.trim_end_matches('/').parse().map_err(|_| invalid());
+    }
+
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let url = url.strip_prefix("www.").unwrap_or(url);
+    let (path, query) = url.split_once('?').ok_or_else(invalid)?;
+    let path = path.trim_end_matches('/');
+    if !matches!(
+        path,
+        "steamcommunity.com/sharedfiles/filedetails" | "steamcommunity.com/workshop/filedetails"
+    ) {
+        return Err(invalid());
+    }
+
+    query
+        .split(['&', '#'])
+        .find_map(|pair| pair.strip_prefix("id="))
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(invalid)
+}
+
+/// A workshop item or collection, with the details retrieved from the Steam Web API.
+#[derive(Debug)]
+pub struct WFile {
+    pub file_id: FileId,
+    pub app_id: FileId,
+    /// Mods and linked collections of a collection in collection order, `None` for a mod.
+    pub children: Option<Vec<FileId>>,
+    /// `sortorder` of each child of a collection, as listed on the workshop.
+    pub sortorders: HashMap<FileId, u32>,
+    pub title: String,
+    pub description: String,
+    pub time_created: DateTime<Utc>,
+    pub time_updated: DateTime<Utc>,
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewOptions {
+    Yes,
+    No,
+    Skip,
+}
+
+impl FromStr for ReviewOptions {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "y" | "yes" => Ok(Self::Yes),
+            "n" | "no" => Ok(Self::No),
+            "skip" => Ok(Self::Skip),
+            _ => Err(color_eyre::eyre::eyre!("invalid input")),
+        }
+    }
+}
+
+impl Display for ReviewOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReviewOptions::Yes => "yes",
+            ReviewOptions::No => "no",
+            ReviewOptions::Skip => "skip",
+        })
+    }
+}
+
+impl WFile {
+    pub fn is_collection(&self) -> bool {
+        self.children.is_some()
+    }
+
+    /// Print a summary of the item, and ask whether to install it if `review` is set.
+    pub fn prompt(&self, review: bool) -> Result<ReviewOptions> {
+        let item = if self.is_collection() {
+            "collection"
+        } else {
+            "mod"
+        };
+        let mut extra = String::new();
+        if let Some(size) = self.metadata.size() {
+            extra.push_str(&format!("\t\t Size: \t {} ", size));
+        }
+        if let Some(subscriptions) = self.metadata.subscriptions {
+            extra.push_str(&format!("\t\t Subscribers: \t {} ", subscriptions));
+        }
+        println!(
+            "Found {} \t\t {}: \t {} \t\t Created at: \t {} \t\t Updated at: \t {} {}",
+            item,
+            self.file_id,
+            self.title,
+            self.time_created.naive_local(),
+            self.time_updated.naive_local(),
+            extra,
+        );
+        if self.metadata.banned == Some(true) {
+            println!("    Warning: {} is banned from the workshop", self.file_id);
+        }
+        if let Some(visibility) = self.metadata.visibility_name().filter(|v| *v != "public") {
+            println!("    Warning: {} is {}", self.file_id, visibility);
+        }
+        let res = if review {
+            let mut input = Input::<ReviewOptions>::new();
+            input.with_prompt("Install? [yes/no/skip]");
+            input.default(ReviewOptions::Yes);
+            input.interact_text()?
+        } else {
+            ReviewOptions::Yes
+        };
+        Ok(res)
+    }
+}
+
+/// The resolved item model: every valid item and collection reachable from the requested file ids.
+#[derive(Debug)]
+pub struct WFiles {
+    pub all_files: HashMap<FileId, WFile>,
+    /// Why each invalid id was left out.
+    pub invalid: HashMap<FileId, EResult>,
+}
+
+impl WFiles {
+    /// Retrieve the details of `files`, plus the mods and linked collections of every collection among them,
+    /// following linked collections up to `max_depth` levels deep. Invalid ids are reported and left out, see [`WFiles::invalid`].
+    pub fn resolve(files: &[FileId], max_depth: usize) -> Result<Self> {
+        // retieve the mod ids of each collection listed in files, following linked collections
+        let mut c_details = Self::resolve_collections(files, max_depth)?;
+        let resolved = c_details.keys().copied().collect::<HashSet<_>>();
+        // linked collections beyond the maximum depth are dropped rather than mistaken for mods
+        let is_wanted = |c: &get_collection_details::Child| {
+            c.is_item() || (c.is_collection() && resolved.contains(&c.file_id))
+        };
+
+        // retrieve details about all mods and collections listed in files, plus all submods and linked collections inside each collection
+        let all_ids = c_details
+            .values()
+            .flat_map(|detail| {
+                let inner = detail
+                    .children
+                    .iter()
+                    .flat_map(|v| v.iter().filter(|c| is_wanted(c)).map(|c| c.file_id));
+                std::iter::once(detail.file_id).chain(inner)
+            })
+            .unique()
+            .collect::<Vec<_>>();
+        let f_details = net::file_details(all_ids.iter().copied())?;
+
+        // a collection lookup that failed other than with "not found", which is the answer for mods,
+        // would leave a collection looking like a mod, so it counts as invalid and is left out too
+        let mut invalid = HashMap::new();
+        for detail in c_details.values() {
+            match EResult::from_code(detail.result) {
+                None | Some(EResult::FileNotFound) => {}
+                Some(error) => {
+                    Self::invalid_id(detail.file_id, error);
+                    invalid.insert(detail.file_id, error);
+                }
+            }
+        }
+        let mut all_files = f_details
+            .details
+            .into_iter()
+            .filter_map(|d| {
+                if invalid.contains_key(&d.file_id) {
+                    return None;
+                }
+                if let Some(error) = d.error() {
+                    Self::invalid_id(d.file_id, error);
+                    invalid.insert(d.file_id, error);
+                    return None;
+                }
+
+                let DetailInner {
+                    app_id,
+                    title,
+                    description,
+                    time_created,
+                    time_updated,
+                    metadata,
+                } = d.inner.unwrap(); // d.inner is guaranteed non-null since d.error() is None
+
+                let file_id = d.file_id;
+                let mut sortorders = HashMap::new();
+                let children = c_details
+                    .get_mut(&file_id)
+                    .and_then(|d| d.children.as_mut())
+                    .map(|children| {
+                        children.sort_by_key(|c| c.sortorder);
+                        children
+                            .iter()
+                            .filter(|c| is_wanted(c))
+                            .map(|c| {
+                                sortorders.insert(c.file_id, c.sortorder);
+                                c.file_id
+                            })
+                            .collect::<Vec<_>>()
+                    });
+
+                let wfile = WFile {
+                    file_id,
+                    app_id,
+                    children,
+                    sortorders,
+                    title,
+                    description,
+                    time_created,
+                    time_updated,
+                    metadata,
+                };
+                Some((file_id, wfile))
+            })
+            .collect::<HashMap<_, _>>();
+
+        // Remove invalid mod ids from collections
+        let valid_ids = all_files.keys().copied().collect::<HashSet<_>>();
+        for file in all_files.values_mut() {
+            if let Some(children) = file.children.as_mut() {
+                children.retain(|id| valid_ids.contains(id));
+            }
+        }
+        Ok(Self { all_files, invalid })
+    }
+
+    /// Walk the collections listed in `files` breadth first, following linked collections up to `max_depth` levels deep.
+    /// Collections already visited are not requested again, so cyclic links terminate.
+    fn resolve_collections(
+        files: &[FileId],
+        max_depth: usize,
+    ) -> Result<HashMap<FileId, get_collection_details::Detail>> {
+        let mut c_details = HashMap::new();
+        let mut pending = files.iter().copied().unique().collect::<Vec<_>>();
+        let mut depth = 0;
+
+        while !pending.is_empty() {
+            let response = get_collection_details::call(pending.iter().copied())?;
+            c_details.extend(response.details);
+
+            let linked = pending
+                .iter()
+                .filter_map(|id| c_details.get(id))
+                .filter(|detail| detail.result == 1)
+                .flat_map(|detail| detail.children.iter().flatten())
+                .filter(|c| c.is_collection())
+                .map(|c| c.file_id)
+                .filter(|id| !c_details.contains_key(id))
+                .unique()
+                .collect::<Vec<_>>();
+
+            if !linked.is_empty() && depth >= max_depth {
+                println!(
+                    "Maximum collection depth {} reached, ignoring linked collections: {}",
+                    max_depth,
+                    linked.iter().join(", ")
+                );
+                break;
+            }
+
+            pending = linked;
+            depth += 1;
+        }
+
+        Ok(c_details)
+    }
+
+    fn invalid_id(id: FileId, error: EResult) {
+        println!("Invalid File ID {}: {}", id, error);
+    }
+
+    /// Fail if any of `files` is invalid, listing the reason for each, as with `--strict`.
+    pub fn ensure_valid(&self, files: &[FileId]) -> Result<()> {
+        let invalid = files
+            .iter()
+            .unique()
+            .filter_map(|id| {
+                self.invalid
+                    .get(id)
+                    .map(|error| format!("{} ({})", id, error))
+            })
+            .collect::<Vec<_>>();
+        if invalid.is_empty() {
+            return Ok(());
+        }
+        Err(color_eyre::eyre::eyre!(
+            "invalid file IDs: {}",
+            invalid.join(", ")
+        ))
+    }
+
+    pub fn get(&self, file_id: &FileId) -> Option<&WFile> {
+        self.all_files.get(file_id)
+    }
+
+    pub fn contains(&self, file_id: &FileId) -> bool {
+        self.all_files.contains_key(file_id)
+    }
+
+    /// Flatten a collection into its mods in collection order, descending into linked collections in place.
+    /// `visited` holds every collection and mod already seen, so each mod is listed once and cycles are cut.
+    /// With `review` set, each mod and linked collection is confirmed through [`WFile::prompt`].
+    pub fn expand_collection<'a>(
+        &'a self,
+        collection: &'a WFile,
+        review: bool,
+        visited: &mut HashSet<FileId>,
+        mods: &mut Vec<&'a WFile>,
+    ) -> Result<()> {
+        if !visited.insert(collection.file_id) {
+            return Ok(());
+        }
+
+        for file_id in collection.children.iter().flatten() {
+            let file = &self.all_files[file_id];
+            if visited.contains(file_id) {
+                continue;
+            }
+
+            match file.prompt(review)? {
+                ReviewOptions::Yes => {
+                    if file.is_collection() {
+                        self.expand_collection(file, review, visited, mods)?;
+                    } else {
+                        visited.insert(file.file_id);
+                        mods.push(file);
+                    }
+                }
+                ReviewOptions::No => {
+                    continue;
+                }
+                ReviewOptions::Skip => {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::mock;
+
+    #[test]
+    fn test_parse_file_id() {
+        for s in [
+            "368330611",
+            " 368330611 ",
+            "https://steamcommunity.com/sharedfiles/filedetails/?id=368330611",
+            "https://steamcommunity.com/sharedfiles/filedetails/?id=368330611&searchtext=",
+            "http://steamcommunity.com/workshop/filedetails/?id=368330611",
+            "https://steamcommunity.com/workshop/filedetails?l=english&id=368330611#comments",
+            "steamcommunity.com/sharedfiles/filedetails/?id=368330611",
+            "steam://url/CommunityFilePage/368330611",
+            "steam://openurl/https://steamcommunity.com/sharedfiles/filedetails/?id=368330611",
+        ] {
+            assert_eq!(parse_file_id(s).unwrap(), 368330611, "{}", s);
+        }
+
+        for s in [
+            "",
+            "abc",
+            "https://steamcommunity.com/sharedfiles/filedetails/",
+            "https://steamcommunity.com/sharedfiles/filedetails/?id=abc",
+            "https://steamcommunity.com/id/someone/?id=368330611",
+            "https://example.com/sharedfiles/filedetails/?id=368330611",
+        ] {
+            assert!(parse_file_id(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_resolve() -> Result<()> {
+        mock::serve();
+        let wfiles = WFiles::resolve(&[368330611, 1111, 1112, 1113], 8)?;
+
+        assert!(!wfiles.contains(&1111));
+        assert_eq!(wfiles.invalid[&1111], EResult::FileNotFound);
+        assert_eq!(wfiles.invalid[&1112], EResult::AccessDenied);
+        // the details of 1113 are fine, but whether it is a collection is unknown
+        assert!(!wfiles.contains(&1113));
+        assert_eq!(wfiles.invalid[&1113], EResult::Busy);
+        assert!(wfiles.ensure_valid(&[368330611]).is_ok());
+        let error = wfiles.ensure_valid(&[368330611, 1112]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid file IDs: 1112 (access denied, the item may be private or friends-only)"
+        );
+        assert_eq!(
+            wfiles.get(&368330611).unwrap().children.as_deref().unwrap(),
+            [818773962, 2009463077, 1541984105, 761421485]
+        );
+        assert_eq!(
+            wfiles
+                .get(&1541984105)
+                .unwrap()
+                .children
+                .as_deref()
+                .unwrap(),
+            [1541438856, 818773962, 368330611]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_cycle() -> Result<()> {
+        mock::serve();
+        let wfiles = WFiles::resolve(&[1541984105], 8)?;
+        let mut mods = Vec::new();
+        wfiles.expand_collection(
+            wfiles.get(&1541984105).unwrap(),
+            false,
+            &mut HashSet::new(),
+            &mut mods,
+        )?;
+
+        assert_eq!(
+            mods.iter().map(|f| f.file_id).collect::<Vec<_>>(),
+            [1541438856, 818773962, 2009463077, 761421485]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! A workspace file holding named sets of mods and collections, e.g. one per server mod pack.
+//!
+//! ```toml
+//! [set.survival]
+//! files = [368330611, 2824342092]
+//! ```
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+pub const WORKSPACE_NAME: &str = "swd.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModSet {
+    /// File ids of the mods and collections in the set, in the order they were added.
+    pub files: Vec<FileId>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Workspace {
+    #[serde(rename = "set", default)]
+    pub sets: BTreeMap<String, ModSet>,
+}
+
+impl Workspace {
+    /// Load a workspace file, or an empty workspace if there is none yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let workspace = toml::from_str(&fs::read_to_string(path)?)?;
+        Ok(workspace)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&ModSet> {
+        self.sets.get(name).ok_or_else(|| {
+            color_eyre::eyre::eyre!(
+                "no set named '{}', the sets are: {}",
+                name,
+                self.sets.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })
+    }
+
+    /// Add file ids to a set, creating it if needed. Returns the ids that were not in the set yet.
+    pub fn add(&mut self, name: &str, files: &[FileId]) -> Vec<FileId> {
+        let set = self.sets.entry(name.to_string()).or_default();
+        let mut added = Vec::new();
+        for &file_id in files {
+            if !set.files.contains(&file_id) {
+                set.files.push(file_id);
+                added.push(file_id);
+            }
+        }
+        added
+    }
+
+    /// Remove file ids from a set, and the set itself once it is empty. Returns the ids that were in the set.
+    pub fn remove(&mut self, name: &str, files: &[FileId]) -> Result<Vec<FileId>> {
+        self.get(name)?;
+        let set = self.sets.get_mut(name).unwrap();
+        let removed = files
+            .iter()
+            .copied()
+            .filter(|id| set.files.contains(id))
+            .collect::<Vec<_>>();
+        set.files.retain(|id| !removed.contains(id));
+        if set.files.is_empty() {
+            self.sets.remove(name);
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempPath;
+
+    #[test]
+    fn test_workspace() -> Result<()> {
+        let path = TempPath::new("workspace.toml");
+
+        let mut workspace = Workspace::load(&path)?;
+        assert_eq!(
+            workspace.add("survival", &[368330611, 2824342092]),
+            [368330611, 2824342092]
+        );
+        assert_eq!(
+            workspace.add("survival", &[2824342092, 1626860092]),
+            [1626860092]
+        );
+        workspace.add("creative", &[818773962]);
+        workspace.save(&path)?;
+
+        let mut loaded = Workspace::load(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(loaded, workspace);
+        assert_eq!(
+            loaded.get("survival")?.files,
+            [368330611, 2824342092, 1626860092]
+        );
+
+        assert_eq!(loaded.remove("survival", &[2824342092, 1])?, [2824342092]);
+        assert_eq!(loaded.remove("creative", &[818773962])?, [818773962]);
+        assert_eq!(loaded.sets.keys().collect::<Vec<_>>(), ["survival"]);
+        assert!(loaded.remove("creative", &[818773962]).is_err());
+        Ok(())
+    }
+}